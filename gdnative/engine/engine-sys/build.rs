@@ -1,4 +1,9 @@
 fn main() {
+    // Without the native feature the crate is pure Rust, and nothing needs to be linked
+    if std::env::var_os("CARGO_FEATURE_NATIVE").is_none() {
+        return;
+    }
+
     let manifest_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
 
     println!(
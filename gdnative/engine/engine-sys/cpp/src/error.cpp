@@ -0,0 +1,15 @@
+#include "export.hpp"
+
+#include <string>
+
+static thread_local std::string last_error;
+
+void engine_set_last_error(const char *message)
+{
+    last_error = message;
+}
+
+ENGINE_API const char *engine_last_error()
+{
+    return last_error.c_str();
+}
@@ -1 +1,36 @@
-#define ENGINE_API extern "C" __declspec(dllexport)
\ No newline at end of file
+#pragma once
+
+#include <exception>
+
+#define ENGINE_API extern "C" __declspec(dllexport)
+
+// Results returned by every fallible export. Exceptions never cross the boundary, instead the
+// message of the last one caught on the calling thread is kept for engine_last_error.
+enum EngineResult
+{
+    ENGINE_OK = 0,
+    ENGINE_EXCEPTION = 1,
+    ENGINE_UNKNOWN_EXCEPTION = 2,
+    ENGINE_NULL_ARGUMENT = 3,
+    ENGINE_OUT_OF_RANGE = 4,
+    // Only returned by the Rust stand-ins used when this library is not linked
+    ENGINE_BACKEND_UNAVAILABLE = 5,
+};
+
+void engine_set_last_error(const char *message);
+
+ENGINE_API const char *engine_last_error();
+
+// Runs the body of an export, converting any exception into an EngineResult
+#define ENGINE_TRY try
+#define ENGINE_CATCH                                   \
+    catch (const std::exception &e)                    \
+    {                                                  \
+        engine_set_last_error(e.what());               \
+        return ENGINE_EXCEPTION;                       \
+    }                                                  \
+    catch (...)                                        \
+    {                                                  \
+        engine_set_last_error("Unknown C++ exception"); \
+        return ENGINE_UNKNOWN_EXCEPTION;               \
+    }
@@ -521,7 +521,7 @@ struct ParticleSolverDescriptor
 {
     Constants constants;
 
-    int particle_count;
+    unsigned int particle_count;
     Particle *particles;
 
     int element_order;
@@ -564,9 +564,23 @@ void initial_curvature(const Vector &point, Vector &curvature)
     curvature = 0.0;
 }
 
-ENGINE_API void *particle_solver_create(ParticleSolverDescriptor desc)
+static void destroy_solver(ParticleSolver *solver)
+{
+    delete solver->ode_solver;
+    delete solver->evolution;
+
+    delete solver->spacetime;
+
+    delete solver->tensor_fe_space;
+    delete solver->scalar_fe_space;
+    delete solver->fec;
+    delete solver->mesh;
+
+    delete solver;
+}
+
+static void create_solver(ParticleSolver *solver, const ParticleSolverDescriptor &desc)
 {
-    ParticleSolver *solver = new ParticleSolver();
     solver->constants = desc.constants;
 
     const int dim = 3;
@@ -666,60 +680,115 @@ ENGINE_API void *particle_solver_create(ParticleSolverDescriptor desc)
 
     solver->evolution = new EvolutionOperator(ess_bdr, solver->scalar_fe_space, solver->tensor_fe_space);
 
-    double t = 0.0;
-    double t_final = 0.5;
-    double dt = 1.0e-2;
-
-    solver->evolution->SetTime(t);
+    solver->evolution->SetTime(0.0);
 
     std::cout << "Inniting" << std::endl;
 
     solver->ode_solver->Init(*solver->evolution);
+}
 
-    std::cout << "ODE Solver Running" << std::endl;
+ENGINE_API int particle_solver_create(ParticleSolverDescriptor desc, void **out_solver)
+{
+    if (out_solver == nullptr || (desc.particles == nullptr && desc.particle_count > 0))
+    {
+        return ENGINE_NULL_ARGUMENT;
+    }
+
+    *out_solver = nullptr;
 
-    bool last_step = false;
-    for (int ti = 1; !last_step; ti++)
+    ENGINE_TRY
     {
-        std::cout << "Step" << std::endl;
+        // Value initialised, so a partially created solver can be destroyed
+        ParticleSolver *solver = new ParticleSolver();
 
-        if (t + dt >= t_final - dt / 2)
+        try
         {
-            last_step = true;
+            create_solver(solver, desc);
+        }
+        catch (...)
+        {
+            destroy_solver(solver);
+            throw;
         }
 
-        solver->ode_solver->Step(*solver->spacetime, t, dt);
+        *out_solver = solver;
     }
+    ENGINE_CATCH
 
-    return solver;
+    return ENGINE_OK;
 }
 
-ENGINE_API void particle_solver_update(void *p_solver, double t, double delta)
+ENGINE_API int particle_solver_update(void *p_solver, double t, double delta)
 {
+    if (p_solver == nullptr)
+    {
+        return ENGINE_NULL_ARGUMENT;
+    }
+
     ParticleSolver *solver = (ParticleSolver *)p_solver;
+
+    ENGINE_TRY
+    {
+        // Only the spacetime is evolved, particles are not advanced through it yet
+        solver->ode_solver->Step(*solver->spacetime, t, delta);
+    }
+    ENGINE_CATCH
+
+    return ENGINE_OK;
 }
 
 ENGINE_API void particle_solver_destroy(void *p_solver)
 {
-    ParticleSolver *solver = (ParticleSolver *)p_solver;
+    if (p_solver != nullptr)
+    {
+        destroy_solver((ParticleSolver *)p_solver);
+    }
+}
 
-    delete solver->ode_solver;
-    delete solver->evolution;
+ENGINE_API unsigned int particle_solver_particle_count(void *p_solver)
+{
+    if (p_solver == nullptr)
+    {
+        return 0;
+    }
 
-    delete solver->spacetime;
+    return (unsigned int)((ParticleSolver *)p_solver)->particles.size();
+}
 
-    delete solver->tensor_fe_space;
-    delete solver->fec;
-    delete solver->mesh;
+ENGINE_API int particle_solver_set_particles(void *p_solver, const Particle *particles, unsigned int count)
+{
+    if (p_solver == nullptr || (particles == nullptr && count > 0))
+    {
+        return ENGINE_NULL_ARGUMENT;
+    }
 
-    delete solver;
+    ParticleSolver *solver = (ParticleSolver *)p_solver;
+
+    ENGINE_TRY
+    {
+        solver->particles.assign(particles, particles + count);
+    }
+    ENGINE_CATCH
+
+    return ENGINE_OK;
 }
 
-ENGINE_API Particle particle_solver_get_particle(void *p_solver, unsigned int index)
+ENGINE_API int particle_solver_get_particle(void *p_solver, unsigned int index, Particle *out_particle)
 {
+    if (p_solver == nullptr || out_particle == nullptr)
+    {
+        return ENGINE_NULL_ARGUMENT;
+    }
+
     ParticleSolver *solver = (ParticleSolver *)p_solver;
 
-    return solver->particles[index];
+    if (index >= solver->particles.size())
+    {
+        return ENGINE_OUT_OF_RANGE;
+    }
+
+    *out_particle = solver->particles[index];
+    return ENGINE_OK;
 }
 
 int main()
@@ -729,7 +798,11 @@ int main()
         ParticleSolverDescriptor desc = ParticleSolverDescriptor{
             Constants{1.0, 1.0}, 0, nullptr, 2, 1.0, 1.0, 1.0, 2};
 
-        void *solver = particle_solver_create(desc);
+        void *solver = nullptr;
+        if (particle_solver_create(desc, &solver) != ENGINE_OK)
+        {
+            std::cerr << engine_last_error() << std::endl;
+        }
         particle_solver_destroy(solver);
     }
     catch (const std::exception &e)
@@ -1,6 +1,7 @@
 use std::os::raw::c_double;
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Constants {
     pub gravitational: c_double,
     pub speed_of_light: c_double,
@@ -0,0 +1,136 @@
+use std::error::Error;
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::c_int;
+
+pub const ENGINE_OK: c_int = 0;
+pub const ENGINE_EXCEPTION: c_int = 1;
+pub const ENGINE_UNKNOWN_EXCEPTION: c_int = 2;
+pub const ENGINE_NULL_ARGUMENT: c_int = 3;
+pub const ENGINE_OUT_OF_RANGE: c_int = 4;
+pub const ENGINE_BACKEND_UNAVAILABLE: c_int = 5;
+
+#[cfg(not(any(feature = "native", feature = "mock", test)))]
+pub use crate::fallback::engine_last_error;
+
+#[cfg(any(feature = "native", feature = "mock", test))]
+extern "C" {
+    /// Message of the last exception caught on the calling thread. The pointer is valid until the
+    /// next call into the library on the same thread.
+    pub fn engine_last_error() -> *const std::os::raw::c_char;
+}
+
+/// An error reported by the C++ library.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EngineError {
+    /// A C++ exception was thrown, with its message
+    Exception(String),
+    /// Something other than a `std::exception` was thrown
+    UnknownException,
+    NullArgument,
+    /// The crate was built without the `native` feature, so there is no C++ library to call
+    BackendUnavailable,
+    OutOfRange {
+        index: usize,
+        count: usize,
+    },
+    /// More particles than the library can address
+    TooManyParticles(usize),
+    /// A result code this crate does not know about
+    Unknown(c_int),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exception(message) => write!(f, "Engine threw an exception: {}", message),
+            Self::UnknownException => write!(f, "Engine threw an unknown exception"),
+            Self::NullArgument => write!(f, "Engine was passed a null argument"),
+            Self::BackendUnavailable => write!(
+                f,
+                "Native engine backend is not available, build with the `native` feature"
+            ),
+            Self::OutOfRange { index, count } => {
+                write!(
+                    f,
+                    "Particle {} is out of range of {} particles",
+                    index, count
+                )
+            }
+            Self::TooManyParticles(count) => write!(f, "Too many particles ({})", count),
+            Self::Unknown(code) => write!(f, "Engine returned unknown result {}", code),
+        }
+    }
+}
+
+impl Error for EngineError {}
+
+/// Converts the result code of an export into a `Result`.
+pub(crate) fn check(code: c_int) -> Result<(), EngineError> {
+    match code {
+        ENGINE_OK => Ok(()),
+        ENGINE_EXCEPTION => Err(EngineError::Exception(last_error())),
+        ENGINE_UNKNOWN_EXCEPTION => Err(EngineError::UnknownException),
+        ENGINE_NULL_ARGUMENT => Err(EngineError::NullArgument),
+        // Only exports taking an index report this, and they are checked with `check_index`
+        ENGINE_OUT_OF_RANGE => Err(EngineError::Unknown(code)),
+        ENGINE_BACKEND_UNAVAILABLE => Err(EngineError::BackendUnavailable),
+        code => Err(EngineError::Unknown(code)),
+    }
+}
+
+/// Converts the result code of an export taking `index` into a `Result`, where `count` gives the
+/// number of items the index was checked against.
+pub(crate) fn check_index(
+    code: c_int,
+    index: usize,
+    count: impl FnOnce() -> usize,
+) -> Result<(), EngineError> {
+    match code {
+        ENGINE_OUT_OF_RANGE => Err(EngineError::OutOfRange {
+            index,
+            count: count(),
+        }),
+        code => check(code),
+    }
+}
+
+fn last_error() -> String {
+    unsafe {
+        let message = engine_last_error();
+        if message.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(message).to_string_lossy().into_owned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_codes_are_checked() {
+        assert_eq!(check(ENGINE_OK), Ok(()));
+        assert_eq!(check(ENGINE_NULL_ARGUMENT), Err(EngineError::NullArgument));
+        assert_eq!(
+            check(ENGINE_BACKEND_UNAVAILABLE),
+            Err(EngineError::BackendUnavailable)
+        );
+        assert_eq!(check(-1), Err(EngineError::Unknown(-1)));
+    }
+
+    #[test]
+    fn out_of_range_indices() {
+        assert_eq!(
+            check_index(ENGINE_OUT_OF_RANGE, 3, || 2),
+            Err(EngineError::OutOfRange { index: 3, count: 2 })
+        );
+        assert_eq!(check_index(ENGINE_OK, 1, || 2), Ok(()));
+        assert_eq!(
+            check_index(ENGINE_NULL_ARGUMENT, 1, || 2),
+            Err(EngineError::NullArgument)
+        );
+    }
+}
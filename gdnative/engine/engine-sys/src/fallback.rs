@@ -0,0 +1,51 @@
+//! Stand-ins for the exports of the C++ library, used when neither it nor the mock is linked.
+//! Creating a solver fails with `ENGINE_BACKEND_UNAVAILABLE`, so none of the other functions ever
+//! see a solver. They keep the signatures of the exports, but accept any argument.
+
+#![allow(clippy::missing_safety_doc)]
+
+use crate::error::{ENGINE_BACKEND_UNAVAILABLE, ENGINE_NULL_ARGUMENT};
+use crate::generic::particle::{Particle, ParticleSolverDescriptor};
+use std::ffi::c_void;
+use std::os::raw::{c_char, c_double, c_int, c_uint};
+
+pub unsafe fn engine_last_error() -> *const c_char {
+    c"".as_ptr()
+}
+
+pub unsafe fn particle_solver_create(
+    _desc: ParticleSolverDescriptor,
+    _out_solver: *mut *mut c_void,
+) -> c_int {
+    ENGINE_BACKEND_UNAVAILABLE
+}
+
+pub unsafe fn particle_solver_update(
+    _solver: *mut c_void,
+    _time: c_double,
+    _delta: c_double,
+) -> c_int {
+    ENGINE_NULL_ARGUMENT
+}
+
+pub unsafe fn particle_solver_particle_count(_solver: *mut c_void) -> c_uint {
+    0
+}
+
+pub unsafe fn particle_solver_set_particles(
+    _solver: *mut c_void,
+    _particles: *const Particle,
+    _count: c_uint,
+) -> c_int {
+    ENGINE_NULL_ARGUMENT
+}
+
+pub unsafe fn particle_solver_get_particle(
+    _solver: *mut c_void,
+    _index: c_uint,
+    _out_particle: *mut Particle,
+) -> c_int {
+    ENGINE_NULL_ARGUMENT
+}
+
+pub unsafe fn particle_solver_destroy(_solver: *mut c_void) {}
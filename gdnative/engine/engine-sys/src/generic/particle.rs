@@ -1,10 +1,11 @@
-#![allow(dead_code)]
-
 use crate::constants::Constants;
+use crate::error::{check, check_index, EngineError};
 use std::ffi::c_void;
-use std::os::raw::{c_double, c_int};
+use std::os::raw::{c_double, c_int, c_uint};
+use std::ptr::{self, NonNull};
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Particle {
     pub x: c_double,
     pub y: c_double,
@@ -19,7 +20,7 @@ pub struct Particle {
 pub struct ParticleSolverDescriptor {
     pub constants: Constants,
 
-    pub particle_count: c_int,
+    pub particle_count: c_uint,
     pub particles: *const Particle,
 
     pub element_order: c_int,
@@ -30,11 +31,209 @@ pub struct ParticleSolverDescriptor {
     pub domain_refinement: c_int,
 }
 
-#[repr(transparent)]
-pub struct ParticleSolver(*mut c_void);
+#[cfg(not(any(feature = "native", feature = "mock", test)))]
+pub use crate::fallback::{
+    particle_solver_create, particle_solver_destroy, particle_solver_get_particle,
+    particle_solver_particle_count, particle_solver_set_particles, particle_solver_update,
+};
 
+#[cfg(any(feature = "native", feature = "mock", test))]
 extern "C" {
-    pub fn particle_solver_create(desc: ParticleSolverDescriptor) -> ParticleSolver;
+    /// Creates a solver, copying the particles of the descriptor. On success the solver is
+    /// written to `out_solver`, and must be destroyed with `particle_solver_destroy`.
+    pub fn particle_solver_create(
+        desc: ParticleSolverDescriptor,
+        out_solver: *mut *mut c_void,
+    ) -> c_int;
+
+    pub fn particle_solver_update(solver: *mut c_void, time: c_double, delta: c_double) -> c_int;
+
+    pub fn particle_solver_particle_count(solver: *mut c_void) -> c_uint;
+
+    /// Replaces the particles of the solver with a copy of `count` particles.
+    pub fn particle_solver_set_particles(
+        solver: *mut c_void,
+        particles: *const Particle,
+        count: c_uint,
+    ) -> c_int;
+
+    pub fn particle_solver_get_particle(
+        solver: *mut c_void,
+        index: c_uint,
+        out_particle: *mut Particle,
+    ) -> c_int;
+
+    pub fn particle_solver_destroy(solver: *mut c_void);
+}
+
+/// Everything a particle solver is created with, apart from its particles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParticleSolverSettings {
+    pub constants: Constants,
+    pub element_order: i32,
+    /// Half extents of the simulated domain
+    pub domain_size: [f64; 3],
+    pub domain_refinement: i32,
+}
+
+/// A particle solver owned by Rust, destroyed when dropped.
+pub struct ParticleSolver {
+    raw: NonNull<c_void>,
+}
+
+// The solver keeps no state tied to the thread which created it, and the functions taking a
+// shared reference only read from it.
+unsafe impl Send for ParticleSolver {}
+unsafe impl Sync for ParticleSolver {}
+
+impl ParticleSolver {
+    /// Creates a solver from a copy of `particles`, which only have to live for the call.
+    pub fn new(
+        settings: &ParticleSolverSettings,
+        particles: &[Particle],
+    ) -> Result<Self, EngineError> {
+        let particle_count = c_uint::try_from(particles.len())
+            .map_err(|_| EngineError::TooManyParticles(particles.len()))?;
+
+        let desc = ParticleSolverDescriptor {
+            constants: settings.constants,
+            particle_count,
+            particles: particles.as_ptr(),
+            element_order: settings.element_order,
+            domain_width: settings.domain_size[0],
+            domain_height: settings.domain_size[1],
+            domain_depth: settings.domain_size[2],
+            domain_refinement: settings.domain_refinement,
+        };
+
+        let mut raw = ptr::null_mut();
+        check(unsafe { particle_solver_create(desc, &mut raw) })?;
+
+        NonNull::new(raw)
+            .map(|raw| Self { raw })
+            .ok_or(EngineError::NullArgument)
+    }
+
+    pub fn update(&mut self, time: f64, delta: f64) -> Result<(), EngineError> {
+        check(unsafe { particle_solver_update(self.raw.as_ptr(), time, delta) })
+    }
+
+    /// Replaces the particles of the solver with a copy of `particles`.
+    pub fn set_particles(&mut self, particles: &[Particle]) -> Result<(), EngineError> {
+        let count = c_uint::try_from(particles.len())
+            .map_err(|_| EngineError::TooManyParticles(particles.len()))?;
+
+        check(unsafe {
+            particle_solver_set_particles(self.raw.as_ptr(), particles.as_ptr(), count)
+        })
+    }
+
+    pub fn particle_count(&self) -> usize {
+        unsafe { particle_solver_particle_count(self.raw.as_ptr()) as usize }
+    }
+
+    pub fn particle(&self, index: usize) -> Result<Particle, EngineError> {
+        let raw_index = c_uint::try_from(index).map_err(|_| EngineError::OutOfRange {
+            index,
+            count: self.particle_count(),
+        })?;
+        let mut particle = Particle::default();
+
+        check_index(
+            unsafe { particle_solver_get_particle(self.raw.as_ptr(), raw_index, &mut particle) },
+            index,
+            || self.particle_count(),
+        )
+        .map(|()| particle)
+    }
+
+    pub fn particles(&self) -> Result<Vec<Particle>, EngineError> {
+        (0..self.particle_count())
+            .map(|index| self.particle(index))
+            .collect()
+    }
+}
+
+impl Drop for ParticleSolver {
+    fn drop(&mut self) {
+        unsafe { particle_solver_destroy(self.raw.as_ptr()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> ParticleSolverSettings {
+        ParticleSolverSettings {
+            constants: Constants {
+                gravitational: 1.0,
+                speed_of_light: 1.0,
+            },
+            element_order: 1,
+            domain_size: [1.0; 3],
+            domain_refinement: 1,
+        }
+    }
+
+    fn pair() -> [Particle; 2] {
+        [
+            Particle {
+                x: 1.0,
+                vely: 0.5,
+                mass: 1.0,
+                ..Particle::default()
+            },
+            Particle {
+                x: -1.0,
+                y: 0.25,
+                velz: -0.5,
+                mass: 3.0,
+                ..Particle::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn particles_round_trip() {
+        let mut solver = ParticleSolver::new(&settings(), &pair()).unwrap();
+
+        assert_eq!(solver.particle_count(), 2);
+        assert_eq!(solver.particles().unwrap(), pair().to_vec());
+
+        solver.update(0.0, 0.01).unwrap();
+        let moved = solver.particles().unwrap();
+
+        // The bodies fall towards each other, keeping their total momentum
+        assert!(moved[0].x < 1.0 && moved[1].x > -1.0);
+        let momentum = |p: &[Particle]| p.iter().map(|p| p.vely * p.mass).sum::<f64>();
+        assert!((momentum(&moved) - momentum(&pair())).abs() < 1e-12);
+
+        solver.set_particles(&pair()[..1]).unwrap();
+        assert_eq!(solver.particles().unwrap(), pair()[..1].to_vec());
+        assert_eq!(
+            solver.particle(1),
+            Err(EngineError::OutOfRange { index: 1, count: 1 })
+        );
+    }
+
+    #[test]
+    fn exceptions_carry_messages() {
+        let invalid = ParticleSolverSettings {
+            element_order: 0,
+            ..settings()
+        };
+
+        match ParticleSolver::new(&invalid, &pair()) {
+            Err(EngineError::Exception(message)) => assert!(message.contains("Element order")),
+            _ => panic!("invalid settings were accepted"),
+        }
 
-    pub fn particle_solver_destroy(solver: ParticleSolver);
+        let mut solver = ParticleSolver::new(&settings(), &[]).unwrap();
+        assert_eq!(solver.particle_count(), 0);
+        assert!(matches!(
+            solver.update(0.0, f64::NAN),
+            Err(EngineError::Exception(_))
+        ));
+    }
 }
@@ -1,2 +1,12 @@
 pub mod constants;
+pub mod error;
 pub mod generic;
+
+#[cfg(all(feature = "native", feature = "mock"))]
+compile_error!("The `native` and `mock` features both provide the engine library");
+
+#[cfg(not(any(feature = "native", feature = "mock", test)))]
+mod fallback;
+
+#[cfg(all(not(feature = "native"), any(feature = "mock", test)))]
+mod mock;
@@ -0,0 +1,173 @@
+//! A Newtonian stand-in for the C++ library, exporting the same C ABI. It is linked in place of
+//! the library by the crate's own tests and by the `mock` feature, so the bindings and the safe
+//! wrappers can be exercised without deal.II or mfem.
+//!
+//! Settings the real library would throw on are reported as exceptions, with a message read
+//! back through `engine_last_error`.
+
+use crate::constants::Constants;
+use crate::error::{ENGINE_EXCEPTION, ENGINE_NULL_ARGUMENT, ENGINE_OK, ENGINE_OUT_OF_RANGE};
+use crate::generic::particle::{Particle, ParticleSolverDescriptor};
+use std::cell::RefCell;
+use std::ffi::{c_void, CString};
+use std::os::raw::{c_char, c_double, c_int, c_uint};
+use std::slice;
+
+struct MockSolver {
+    constants: Constants,
+    particles: Vec<Particle>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Records the message of a failure, as the C++ library does when it catches an exception.
+fn throw(message: &str) -> c_int {
+    let message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+    ENGINE_EXCEPTION
+}
+
+#[no_mangle]
+extern "C" fn engine_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+/// Copies `count` particles, which may only be null when there are none.
+unsafe fn copy_particles(particles: *const Particle, count: usize) -> Vec<Particle> {
+    if count == 0 {
+        Vec::new()
+    } else {
+        slice::from_raw_parts(particles, count).to_vec()
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn particle_solver_create(
+    desc: ParticleSolverDescriptor,
+    out_solver: *mut *mut c_void,
+) -> c_int {
+    if out_solver.is_null() || (desc.particles.is_null() && desc.particle_count > 0) {
+        return ENGINE_NULL_ARGUMENT;
+    }
+
+    *out_solver = std::ptr::null_mut();
+
+    if desc.element_order < 1 {
+        return throw("Element order must be at least 1");
+    }
+    if desc.domain_refinement < 0 {
+        return throw("Domain refinement must not be negative");
+    }
+    if !(desc.domain_width > 0.0 && desc.domain_height > 0.0 && desc.domain_depth > 0.0) {
+        return throw("Domain must have a positive size");
+    }
+
+    let solver = MockSolver {
+        constants: desc.constants,
+        particles: copy_particles(desc.particles, desc.particle_count as usize),
+    };
+
+    *out_solver = Box::into_raw(Box::new(solver)) as *mut c_void;
+    ENGINE_OK
+}
+
+/// Advances the particles with a kick-drift-kick leapfrog under Newtonian gravity.
+#[no_mangle]
+unsafe extern "C" fn particle_solver_update(
+    solver: *mut c_void,
+    _time: c_double,
+    delta: c_double,
+) -> c_int {
+    let solver = match (solver as *mut MockSolver).as_mut() {
+        Some(solver) => solver,
+        None => return ENGINE_NULL_ARGUMENT,
+    };
+
+    if !delta.is_finite() {
+        return throw("Time step must be finite");
+    }
+
+    let g = solver.constants.gravitational;
+    let kick = |particles: &mut [Particle]| {
+        let snapshot = particles.to_vec();
+        for particle in particles.iter_mut() {
+            for other in snapshot.iter() {
+                let (dx, dy, dz) = (
+                    other.x - particle.x,
+                    other.y - particle.y,
+                    other.z - particle.z,
+                );
+                let r_sq = dx * dx + dy * dy + dz * dz;
+                if r_sq < 1.0e-20 {
+                    continue;
+                }
+
+                let scale = g * other.mass / (r_sq * r_sq.sqrt()) * (0.5 * delta);
+                particle.velx += dx * scale;
+                particle.vely += dy * scale;
+                particle.velz += dz * scale;
+            }
+        }
+    };
+
+    kick(&mut solver.particles);
+    for particle in solver.particles.iter_mut() {
+        particle.x += particle.velx * delta;
+        particle.y += particle.vely * delta;
+        particle.z += particle.velz * delta;
+    }
+    kick(&mut solver.particles);
+
+    ENGINE_OK
+}
+
+#[no_mangle]
+unsafe extern "C" fn particle_solver_set_particles(
+    solver: *mut c_void,
+    particles: *const Particle,
+    count: c_uint,
+) -> c_int {
+    if solver.is_null() || (particles.is_null() && count > 0) {
+        return ENGINE_NULL_ARGUMENT;
+    }
+
+    (*(solver as *mut MockSolver)).particles = copy_particles(particles, count as usize);
+    ENGINE_OK
+}
+
+#[no_mangle]
+unsafe extern "C" fn particle_solver_particle_count(solver: *mut c_void) -> c_uint {
+    match (solver as *const MockSolver).as_ref() {
+        Some(solver) => solver.particles.len() as c_uint,
+        None => 0,
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn particle_solver_get_particle(
+    solver: *mut c_void,
+    index: c_uint,
+    out_particle: *mut Particle,
+) -> c_int {
+    let solver = match (solver as *const MockSolver).as_ref() {
+        Some(solver) if !out_particle.is_null() => solver,
+        _ => return ENGINE_NULL_ARGUMENT,
+    };
+
+    match solver.particles.get(index as usize) {
+        Some(particle) => {
+            *out_particle = *particle;
+            ENGINE_OK
+        }
+        None => ENGINE_OUT_OF_RANGE,
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn particle_solver_destroy(solver: *mut c_void) {
+    if !solver.is_null() {
+        drop(Box::from_raw(solver as *mut MockSolver));
+    }
+}
@@ -0,0 +1,240 @@
+use super::{Inspected, System, SystemConfig, SystemNode};
+use hashbrown::HashMap;
+use hecs::World;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::any::Any;
+use std::sync::{OnceLock, RwLock};
+
+/// A system which can be stored behind a `Subsystem`. The key identifies the type in saved files,
+/// so it must never change once files have been written with it.
+pub trait RegisteredSystem: System {
+    const KEY: &'static str;
+}
+
+/// Object safe counterpart to `System`. Every `SystemNode` of a registered system implements it,
+/// which allows a parent to drive children of types it knows nothing about.
+pub trait DynSystem: Send + Sync + Any {
+    fn key(&self) -> &'static str;
+
+    fn solve_begin(&mut self, config: &SystemConfig, time: f64);
+
+    fn solve_update(&mut self, config: &SystemConfig, time: f64, delta: f64);
+
+    fn solve_end(&mut self, config: &SystemConfig, time: f64);
+
+    fn view_begin(&mut self, config: &SystemConfig, time: f64);
+
+    fn view_set_time(&mut self, config: &SystemConfig, time: f64);
+
+    fn view_end(&mut self, config: &SystemConfig, time: f64);
+
+    fn edit_begin(&mut self, config: &SystemConfig);
+
+    fn edit_end(&mut self, config: &SystemConfig);
+
+    fn invalidate(&mut self, config: &SystemConfig);
+
+    fn reset(&mut self, config: &SystemConfig);
+
+    fn inspect(&self) -> Vec<Inspected>;
+
+    fn children(&self) -> &World;
+
+    fn children_mut(&mut self) -> &mut World;
+
+    fn to_bytes(&self) -> Result<Vec<u8>, bincode::Error>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<S: RegisteredSystem> DynSystem for SystemNode<S> {
+    fn key(&self) -> &'static str {
+        S::KEY
+    }
+
+    fn solve_begin(&mut self, config: &SystemConfig, time: f64) {
+        SystemNode::solve_begin(self, config, time);
+    }
+
+    fn solve_update(&mut self, config: &SystemConfig, time: f64, delta: f64) {
+        SystemNode::solve_update(self, config, time, delta);
+    }
+
+    fn solve_end(&mut self, config: &SystemConfig, time: f64) {
+        SystemNode::solve_end(self, config, time);
+    }
+
+    fn view_begin(&mut self, config: &SystemConfig, time: f64) {
+        SystemNode::view_begin(self, config, time);
+    }
+
+    fn view_set_time(&mut self, config: &SystemConfig, time: f64) {
+        SystemNode::view_set_time(self, config, time);
+    }
+
+    fn view_end(&mut self, config: &SystemConfig, time: f64) {
+        SystemNode::view_end(self, config, time);
+    }
+
+    fn edit_begin(&mut self, config: &SystemConfig) {
+        SystemNode::edit_begin(self, config);
+    }
+
+    fn edit_end(&mut self, config: &SystemConfig) {
+        SystemNode::edit_end(self, config);
+    }
+
+    fn invalidate(&mut self, config: &SystemConfig) {
+        SystemNode::invalidate(self, config);
+    }
+
+    fn reset(&mut self, config: &SystemConfig) {
+        SystemNode::reset(self, config);
+    }
+
+    fn inspect(&self) -> Vec<Inspected> {
+        SystemNode::inspect(self)
+    }
+
+    fn children(&self) -> &World {
+        SystemNode::children(self)
+    }
+
+    fn children_mut(&mut self) -> &mut World {
+        SystemNode::children_mut(self)
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A component holding a child system of any registered type.
+pub struct Subsystem(Box<dyn DynSystem>);
+
+impl Subsystem {
+    pub fn new<S: RegisteredSystem>(node: SystemNode<S>) -> Self {
+        Self(Box::new(node))
+    }
+
+    pub fn key(&self) -> &'static str {
+        self.0.key()
+    }
+
+    pub fn get(&self) -> &dyn DynSystem {
+        self.0.as_ref()
+    }
+
+    pub fn get_mut(&mut self) -> &mut dyn DynSystem {
+        self.0.as_mut()
+    }
+
+    pub fn is<S: System>(&self) -> bool {
+        self.0.as_any().is::<SystemNode<S>>()
+    }
+
+    pub fn downcast_ref<S: System>(&self) -> Option<&SystemNode<S>> {
+        self.0.as_any().downcast_ref()
+    }
+
+    pub fn downcast_mut<S: System>(&mut self) -> Option<&mut SystemNode<S>> {
+        self.0.as_any_mut().downcast_mut()
+    }
+}
+
+impl Serialize for Subsystem {
+    fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+    where
+        Ser: Serializer,
+    {
+        use serde::ser::Error;
+
+        let bytes = self.0.to_bytes().map_err(Ser::Error::custom)?;
+        (self.key(), bytes).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Subsystem {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use de::Error;
+
+        let (key, bytes) = <(String, Vec<u8>)>::deserialize(deserializer)?;
+
+        // The registry is released before constructing, as the system may contain subsystems of
+        // its own.
+        let constructor = registry()
+            .read()
+            .unwrap()
+            .constructor(&key)
+            .ok_or_else(|| D::Error::custom(format!("unregistered system type `{}`", key)))?;
+
+        Ok(Subsystem(constructor(&bytes).map_err(D::Error::custom)?))
+    }
+}
+
+type Constructor = fn(&[u8]) -> Result<Box<dyn DynSystem>, bincode::Error>;
+
+/// Maps the keys of registered systems to functions which deserialize them.
+pub struct SystemRegistry {
+    constructors: HashMap<&'static str, Constructor>,
+}
+
+impl SystemRegistry {
+    fn new() -> Self {
+        Self {
+            constructors: HashMap::new(),
+        }
+    }
+
+    pub fn register<S: RegisteredSystem>(&mut self) {
+        fn construct<S: RegisteredSystem>(
+            bytes: &[u8],
+        ) -> Result<Box<dyn DynSystem>, bincode::Error> {
+            Ok(Box::new(bincode::deserialize::<SystemNode<S>>(bytes)?))
+        }
+
+        self.constructors.insert(S::KEY, construct::<S>);
+    }
+
+    pub fn is_registered(&self, key: &str) -> bool {
+        self.constructors.contains_key(key)
+    }
+
+    fn constructor(&self, key: &str) -> Option<Constructor> {
+        self.constructors.get(key).copied()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.constructors.keys().copied()
+    }
+}
+
+/// The global registry used when loading subsystems. Systems built into the engine are registered
+/// on first access.
+pub fn registry() -> &'static RwLock<SystemRegistry> {
+    static REGISTRY: OnceLock<RwLock<SystemRegistry>> = OnceLock::new();
+
+    REGISTRY.get_or_init(|| {
+        let mut registry = SystemRegistry::new();
+        crate::gravity::register_systems(&mut registry);
+        crate::field::register_systems(&mut registry);
+        RwLock::new(registry)
+    })
+}
+
+pub fn register_system<S: RegisteredSystem>() {
+    registry().write().unwrap().register::<S>();
+}
@@ -0,0 +1,197 @@
+use super::{Config, Root, Subsystem, System, SystemConfig, SystemNode};
+use hecs::Entity;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum EditError {
+    #[error("Body {0} does not exist")]
+    BodyNotFound(usize),
+    #[error("Body {0} already exists")]
+    DuplicateBody(usize),
+    #[error("Invalid {property} for body {index}")]
+    InvalidValue {
+        index: usize,
+        property: &'static str,
+    },
+    #[error("Tree has no subsystem which can hold the edit")]
+    SubsystemNotFound,
+    #[error("No transaction is open")]
+    NoTransaction,
+    #[error("A transaction is already open")]
+    TransactionOpen,
+    #[error("Nothing to undo")]
+    NothingToUndo,
+    #[error("Nothing to redo")]
+    NothingToRedo,
+    #[error("Tree cannot be edited while it is solving")]
+    Solving,
+}
+
+/// A reversible edit of a tree. `undo` is only called after a successful `apply`, and `apply` is
+/// called again to redo the command.
+pub trait Command<R: System + Root>: Send + Sync {
+    fn apply(
+        &mut self,
+        root: &mut SystemNode<R>,
+        config: &mut SystemConfig,
+    ) -> Result<(), EditError>;
+
+    fn undo(&mut self, root: &mut SystemNode<R>, config: &mut SystemConfig);
+
+    /// Whether the command changes the initial conditions of the tree, so anything recorded
+    /// before it has to be solved again.
+    fn invalidates(&self) -> bool {
+        true
+    }
+}
+
+/// Commands which are undone and redone together.
+pub(super) struct Transaction<R: System + Root> {
+    pub(super) commands: Vec<Box<dyn Command<R>>>,
+    /// Revision of the tree before and after the transaction
+    pub(super) before: u64,
+    pub(super) after: u64,
+}
+
+impl<R: System + Root> Transaction<R> {
+    pub(super) fn invalidates(&self) -> bool {
+        self.commands.iter().any(|command| command.invalidates())
+    }
+}
+
+/// Undo and redo stacks of a tree. Histories only live as long as the tree is loaded.
+pub(super) struct EditHistory<R: System + Root> {
+    pub(super) undo: Vec<Transaction<R>>,
+    pub(super) redo: Vec<Transaction<R>>,
+    pub(super) open: Option<Transaction<R>>,
+    /// Identifies the current initial conditions of the tree
+    pub(super) revision: u64,
+    next_revision: u64,
+    /// Revision the records of the tree were solved from
+    pub(super) solved: Option<u64>,
+}
+
+impl<R: System + Root> Default for EditHistory<R> {
+    fn default() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            open: None,
+            revision: 0,
+            next_revision: 1,
+            solved: None,
+        }
+    }
+}
+
+impl<R: System + Root> EditHistory<R> {
+    pub(super) fn begin(&mut self) -> Result<(), EditError> {
+        if self.open.is_some() {
+            return Err(EditError::TransactionOpen);
+        }
+
+        self.open = Some(Transaction {
+            commands: Vec::new(),
+            before: self.revision,
+            after: self.revision,
+        });
+
+        Ok(())
+    }
+
+    /// Closes the open transaction, pushing it onto the undo stack if it changed anything.
+    pub(super) fn commit(&mut self) -> Result<(), EditError> {
+        let mut transaction = self.open.take().ok_or(EditError::NoTransaction)?;
+
+        if transaction.commands.is_empty() {
+            return Ok(());
+        }
+
+        if transaction
+            .commands
+            .iter()
+            .any(|command| command.invalidates())
+        {
+            transaction.after = self.next_revision;
+            self.next_revision += 1;
+            self.revision = transaction.after;
+        }
+
+        self.undo.push(transaction);
+        self.redo.clear();
+
+        Ok(())
+    }
+}
+
+/// Adds a subsystem to the children of the root.
+pub struct AddSubsystem {
+    subsystem: Option<Subsystem>,
+    entity: Option<Entity>,
+}
+
+impl AddSubsystem {
+    pub fn new(subsystem: Subsystem) -> Self {
+        Self {
+            subsystem: Some(subsystem),
+            entity: None,
+        }
+    }
+}
+
+impl<R: System + Root> Command<R> for AddSubsystem {
+    fn apply(
+        &mut self,
+        root: &mut SystemNode<R>,
+        _config: &mut SystemConfig,
+    ) -> Result<(), EditError> {
+        if let Some(subsystem) = self.subsystem.take() {
+            self.entity = Some(root.children_mut().spawn((subsystem,)));
+        }
+
+        Ok(())
+    }
+
+    fn undo(&mut self, root: &mut SystemNode<R>, _config: &mut SystemConfig) {
+        if let Some(entity) = self.entity.take() {
+            self.subsystem = root.children_mut().remove_one::<Subsystem>(entity).ok();
+            let _ = root.children_mut().despawn(entity);
+        }
+    }
+}
+
+/// Replaces a config of the tree, such as its units.
+pub struct SetConfig<C: Config + Clone> {
+    config: C,
+    previous: Option<Option<C>>,
+}
+
+impl<C: Config + Clone> SetConfig<C> {
+    pub fn new(config: C) -> Self {
+        Self {
+            config,
+            previous: None,
+        }
+    }
+}
+
+impl<R: System + Root, C: Config + Clone> Command<R> for SetConfig<C> {
+    fn apply(
+        &mut self,
+        _root: &mut SystemNode<R>,
+        config: &mut SystemConfig,
+    ) -> Result<(), EditError> {
+        self.previous = Some(config.get::<C>().cloned());
+        config.insert(self.config.clone());
+
+        Ok(())
+    }
+
+    fn undo(&mut self, _root: &mut SystemNode<R>, config: &mut SystemConfig) {
+        match self.previous.take() {
+            Some(Some(previous)) => config.insert(previous),
+            Some(None) => config.remove::<C>(),
+            None => {}
+        }
+    }
+}
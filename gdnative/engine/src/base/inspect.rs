@@ -0,0 +1,78 @@
+use super::Subsystem;
+use glam::DVec3;
+use hecs::World;
+
+/// A value of a component field, as shown by an inspector.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Vector(DVec3),
+    Text(String),
+}
+
+/// A component of an inspected item along with the values of its fields.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InspectedComponent {
+    pub name: &'static str,
+    pub fields: Vec<(&'static str, Value)>,
+}
+
+impl InspectedComponent {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, field: &'static str, value: Value) -> Self {
+        self.fields.push((field, value));
+        self
+    }
+}
+
+/// An item in the outline of a tree, either a subsystem or one of the objects it simulates. Ids
+/// are only unique among siblings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inspected {
+    pub id: u64,
+    pub kind: &'static str,
+    /// Number of entities in the children of a subsystem, or zero for other items
+    pub child_count: usize,
+    pub components: Vec<InspectedComponent>,
+    pub children: Vec<Inspected>,
+}
+
+impl Inspected {
+    pub fn new(id: u64, kind: &'static str) -> Self {
+        Self {
+            id,
+            kind,
+            child_count: 0,
+            components: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Inspects every subsystem in `children`, identified by the id of its entity.
+pub fn inspect_subsystems(children: &World) -> Vec<Inspected> {
+    let mut items = children
+        .query::<&Subsystem>()
+        .iter()
+        .map(|(entity, subsystem)| {
+            let system = subsystem.get();
+
+            Inspected {
+                child_count: system.children().len() as usize,
+                children: system.inspect(),
+                ..Inspected::new(entity.id() as u64, system.key())
+            }
+        })
+        .collect::<Vec<_>>();
+
+    items.sort_by_key(|item| item.id);
+    items
+}
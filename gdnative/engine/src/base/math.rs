@@ -10,9 +10,49 @@ pub trait AbstractVector: Clone {
         self.add(other);
     }
 
+    /// Interpolates towards `b`, where `x` of zero leaves the vector unchanged and one gives `b`.
     fn lerp(&mut self, mut b: Self, x: f64) {
-        self.scale(x);
-        b.scale(1.0 - x);
+        self.scale(1.0 - x);
+        b.scale(x);
         self.add(b);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Scalar(f64);
+
+    impl AbstractVector for Scalar {
+        fn zero() -> Self {
+            Self(0.0)
+        }
+
+        fn one() -> Self {
+            Self(1.0)
+        }
+
+        fn add(&mut self, other: Self) {
+            self.0 += other.0;
+        }
+
+        fn scale(&mut self, scalar: f64) {
+            self.0 *= scalar;
+        }
+    }
+
+    #[test]
+    fn lerp_from_first_to_second() {
+        let lerp = |x| {
+            let mut a = Scalar(2.0);
+            a.lerp(Scalar(6.0), x);
+            a
+        };
+
+        assert_eq!(lerp(0.0), Scalar(2.0));
+        assert_eq!(lerp(0.25), Scalar(3.0));
+        assert_eq!(lerp(1.0), Scalar(6.0));
+    }
+}
@@ -1,16 +1,34 @@
+mod dynamic;
+mod edit;
+mod inspect;
 mod math;
 mod node;
 mod record;
+mod schedule;
+mod solve;
+mod spatial;
 mod tree;
 
-use serde::{Deserializer, Serializer};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::any::Any;
 
+pub use dynamic::{
+    register_system, registry, DynSystem, RegisteredSystem, Subsystem, SystemRegistry,
+};
+pub use edit::{AddSubsystem, Command, EditError, SetConfig};
 pub use hecs::{Entity, World};
+pub use inspect::{inspect_subsystems, Inspected, InspectedComponent, Value};
 pub use math::AbstractVector;
 pub use node::SystemNode;
 pub use record::ContinuousRecord;
-pub use tree::{Config, SystemConfig, SystemTree};
+pub use schedule::{Event, Schedule, ScheduledEvent};
+pub use solve::{SolveHandle, SolveObserver, SolveOutcome, SolveProgress};
+pub use spatial::{RayHit, RayTolerance, SpatialIndex};
+pub use tree::{
+    config_registry, register_config, CheckpointError, Config, ConfigRegistry, PersistentConfig,
+    SolveError, SolveState, SystemConfig, SystemTree,
+};
 
 pub trait Object: Send + Sync + Any {}
 
@@ -27,6 +45,24 @@ pub trait System: Send + Sync + Sized + Any {
 
     fn view_end(&mut self, children: &mut World, config: &SystemConfig, time: f64);
 
+    /// Called before a command edits the system or its children.
+    fn edit_begin(&mut self, _children: &mut World, _config: &SystemConfig) {}
+
+    /// Called after a command has edited the system or its children.
+    fn edit_end(&mut self, _children: &mut World, _config: &SystemConfig) {}
+
+    /// Discards everything recorded by previous solves, as the initial conditions have changed.
+    fn invalidate(&mut self, _children: &mut World, _config: &SystemConfig) {}
+
+    /// Returns the children to the initial conditions of the last solve, undoing everything the
+    /// solve and its events changed. Records are kept.
+    fn reset(&mut self, _children: &mut World, _config: &SystemConfig) {}
+
+    /// Describes the children of the system for an inspector.
+    fn inspect(&self, _children: &World) -> Vec<Inspected> {
+        Vec::new()
+    }
+
     fn serialize_system<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer;
@@ -45,15 +81,33 @@ pub trait System: Send + Sync + Sized + Any {
 }
 
 pub trait Root {
+    /// Events which can be scheduled on a tree with this root
+    type Event: Clone + Send + Sync + Serialize + DeserializeOwned;
+
     fn default_config() -> SystemConfig;
 
+    /// Applies a scheduled event to the tree at `time`, part way through a solve.
+    fn apply_event(
+        root: &mut SystemNode<Self>,
+        config: &SystemConfig,
+        time: f64,
+        event: &Self::Event,
+    ) where
+        Self: System;
+
     fn serialize_config<S>(config: &SystemConfig, serializer: S) -> Result<S::Ok, S::Error>
     where
-        S: Serializer;
+        S: Serializer,
+    {
+        config.serialize(serializer)
+    }
 
     fn deserialize_config<'de, D>(deserializer: D) -> Result<SystemConfig, D::Error>
     where
-        D: Deserializer<'de>;
+        D: Deserializer<'de>,
+    {
+        SystemConfig::deserialize(deserializer)
+    }
 }
 
 #[cfg(test)]
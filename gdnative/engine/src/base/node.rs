@@ -1,9 +1,10 @@
-use super::{System, SystemConfig};
+use super::{Inspected, System, SystemConfig};
 use hecs::World;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::marker::PhantomData;
 
 #[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct SystemNode<S: System> {
     system: SystemWrapper<S>,
     children: ChildrenWrapper<S>,
@@ -17,13 +18,21 @@ impl<S: System> SystemNode<S> {
         }
     }
 
-    // pub fn edit_begin(&mut self, config: &SystemConfig) {
-    //     self.system.0.edit_begin(&mut self.children.0, config);
-    // }
+    pub fn edit_begin(&mut self, config: &SystemConfig) {
+        self.system.0.edit_begin(&mut self.children.0, config);
+    }
+
+    pub fn edit_end(&mut self, config: &SystemConfig) {
+        self.system.0.edit_end(&mut self.children.0, config);
+    }
+
+    pub fn invalidate(&mut self, config: &SystemConfig) {
+        self.system.0.invalidate(&mut self.children.0, config);
+    }
 
-    // pub fn edit_end(&mut self, config: &SystemConfig) {
-    //     self.system.0.edit_end(&mut self.children.0, config);
-    // }
+    pub fn reset(&mut self, config: &SystemConfig) {
+        self.system.0.reset(&mut self.children.0, config);
+    }
 
     pub fn solve_begin(&mut self, config: &SystemConfig, time: f64) {
         self.system
@@ -55,6 +64,10 @@ impl<S: System> SystemNode<S> {
         self.system.0.view_end(&mut self.children.0, config, time);
     }
 
+    pub fn inspect(&self) -> Vec<Inspected> {
+        self.system.0.inspect(&self.children.0)
+    }
+
     pub fn get(&self) -> &S {
         &self.system.0
     }
@@ -1,6 +1,7 @@
 use super::math::AbstractVector;
 use serde::{Deserialize, Serialize};
 use std::any::Any;
+use std::collections::VecDeque;
 
 // #[derive(Error, Debug)]
 // pub enum RecordContextError {
@@ -45,43 +46,98 @@ use std::any::Any;
 
 #[derive(Serialize, Deserialize)]
 pub struct ContinuousRecord<V: Send + Sync + Clone + AbstractVector + Any> {
-    times: Vec<f64>,
-    values: Vec<V>,
+    /// Sample times in increasing order, whichever direction they were solved in
+    times: VecDeque<f64>,
+    values: VecDeque<V>,
 }
 
 impl<V: Send + Sync + Clone + AbstractVector + Any> ContinuousRecord<V> {
     pub fn new() -> Self {
         Self {
-            times: Vec::new(),
-            values: Vec::new(),
+            times: VecDeque::new(),
+            values: VecDeque::new(),
         }
     }
 
+    /// Adds a value to the record. Solving forwards appends values and solving backwards prepends
+    /// them. Saving twice at the same time, as happens when a solve is extended, replaces the
+    /// previous value.
     pub fn save(&mut self, time: f64, value: V) {
-        self.times.push(time);
-        self.values.push(value);
+        let index = self.times.partition_point(|&t| t < time);
+
+        if self.times.get(index) == Some(&time) {
+            self.values[index] = value;
+        } else {
+            // Inserting at either end of a deque is cheap
+            self.times.insert(index, time);
+            self.values.insert(index, value);
+        }
     }
 
+    /// Interpolates the record at a given time. Times outside the record give zero.
     pub fn load(&self, time: f64) -> V {
-        let mut index = -1;
+        let next = self.times.partition_point(|&t| t <= time);
 
-        for i in 0..(self.times.len() - 1) {
-            if time >= self.times[i] && time < self.times[i + 1] {
-                index = i as i32;
-            }
+        if next == 0 {
+            return V::zero();
         }
 
-        if index == -1 {
-            return V::zero();
+        if next == self.times.len() {
+            return if self.times[next - 1] == time {
+                self.values[next - 1].clone()
+            } else {
+                V::zero()
+            };
         }
 
-        let interp = (time - self.times[index as usize])
-            / (self.times[index as usize + 1] - self.times[index as usize]);
+        let index = next - 1;
+        let interp = (time - self.times[index]) / (self.times[index + 1] - self.times[index]);
 
-        let mut v = self.values[index as usize].clone();
-        v.lerp(self.values[index as usize + 1].clone(), interp);
+        let mut v = self.values[index].clone();
+        v.lerp(self.values[index + 1].clone(), interp);
         v
     }
+
+    /// The first and last times of the record.
+    pub fn span(&self) -> Option<(f64, f64)> {
+        Some((*self.times.front()?, *self.times.back()?))
+    }
+
+    pub fn contains(&self, time: f64) -> bool {
+        self.span()
+            .is_some_and(|(start, end)| time >= start && time <= end)
+    }
+
+    /// The samples of the record between `start` and `end`, in increasing time. Ends of the
+    /// window which fall inside the record are interpolated, so the samples cover the window
+    /// exactly where the record allows.
+    pub fn samples(&self, start: f64, end: f64) -> Vec<(f64, V)> {
+        let (start, end) = (start.min(end), start.max(end));
+
+        let (first, last) = match self.span() {
+            Some((first, last)) if first <= end && last >= start => (first, last),
+            _ => return Vec::new(),
+        };
+
+        let begin = self.times.partition_point(|&t| t <= start);
+        let finish = self.times.partition_point(|&t| t < end);
+
+        let mut samples = Vec::with_capacity(finish.saturating_sub(begin) + 2);
+
+        if start >= first {
+            samples.push((start, self.load(start)));
+        }
+
+        for i in begin..finish {
+            samples.push((self.times[i], self.values[i].clone()));
+        }
+
+        if end <= last && end > start {
+            samples.push((end, self.load(end)));
+        }
+
+        samples
+    }
 }
 
 // #[derive(Serialize, Deserialize)]
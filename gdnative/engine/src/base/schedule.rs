@@ -0,0 +1,88 @@
+use super::tree::PersistentConfig;
+use serde::{Deserialize, Serialize};
+
+/// Something which happens to a tree at a fixed point in time during a solve.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Event<E> {
+    /// An event handled by the root system of the tree
+    System(E),
+    /// Replaces a persistent config, stored by its key so it can be saved with the schedule
+    Config { key: String, bytes: Vec<u8> },
+}
+
+impl<E> Event<E> {
+    pub fn config<C: PersistentConfig>(config: &C) -> Result<Self, bincode::Error> {
+        Ok(Event::Config {
+            key: String::from(C::KEY),
+            bytes: bincode::serialize(config)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledEvent<E> {
+    pub time: f64,
+    pub event: Event<E>,
+}
+
+/// Events ordered by time. Events at the same time are applied in the order they were inserted.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Schedule<E> {
+    events: Vec<ScheduledEvent<E>>,
+}
+
+impl<E> Default for Schedule<E> {
+    fn default() -> Self {
+        Self { events: Vec::new() }
+    }
+}
+
+impl<E> Schedule<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts an event, returning its position in the schedule.
+    pub fn insert(&mut self, time: f64, event: Event<E>) -> usize {
+        let index = self
+            .events
+            .partition_point(|scheduled| scheduled.time <= time);
+        self.events.insert(index, ScheduledEvent { time, event });
+        index
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<ScheduledEvent<E>> {
+        if index < self.events.len() {
+            Some(self.events.remove(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ScheduledEvent<E>> {
+        self.events.iter()
+    }
+
+    /// Events with `start <= time < end`, in the order they should be applied.
+    pub fn between(&self, start: f64, end: f64) -> &[ScheduledEvent<E>] {
+        let first = self
+            .events
+            .partition_point(|scheduled| scheduled.time < start);
+        let last = self
+            .events
+            .partition_point(|scheduled| scheduled.time < end);
+        &self.events[first..last.max(first)]
+    }
+}
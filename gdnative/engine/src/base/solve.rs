@@ -0,0 +1,108 @@
+use super::SolveState;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A snapshot of how far a solve has come.
+#[derive(Clone, Copy, Debug)]
+pub struct SolveProgress {
+    pub start: f64,
+    pub end: f64,
+    /// Simulated time reached by the last completed step
+    pub time: f64,
+    pub step: usize,
+    pub steps: usize,
+    pub elapsed: Duration,
+    /// Estimated wall clock time until the solve completes
+    pub remaining: Option<Duration>,
+}
+
+impl SolveProgress {
+    pub(super) fn new(state: &SolveState, began: Instant) -> Self {
+        let elapsed = began.elapsed();
+
+        let remaining = if state.step > 0 {
+            Some(elapsed.mul_f64((state.steps - state.step) as f64 / state.step as f64))
+        } else {
+            None
+        };
+
+        Self {
+            start: state.start,
+            end: state.end,
+            time: state.time,
+            step: state.step,
+            steps: state.steps,
+            elapsed,
+            remaining,
+        }
+    }
+
+    /// Fraction of steps completed, between zero and one.
+    pub fn fraction(&self) -> f64 {
+        if self.steps == 0 {
+            1.0
+        } else {
+            self.step as f64 / self.steps as f64
+        }
+    }
+}
+
+/// How a solve driven by `SystemTree::solve_observed` finished.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SolveOutcome {
+    Completed,
+    /// The solve was cancelled, and the tree's records end at the given time.
+    Cancelled(f64),
+}
+
+/// Receives progress reports from a solve.
+pub trait SolveObserver {
+    fn on_progress(&mut self, progress: &SolveProgress);
+}
+
+impl<F: FnMut(&SolveProgress)> SolveObserver for F {
+    fn on_progress(&mut self, progress: &SolveProgress) {
+        self(progress)
+    }
+}
+
+impl SolveObserver for Sender<SolveProgress> {
+    fn on_progress(&mut self, progress: &SolveProgress) {
+        // A dropped receiver just means no one is listening anymore.
+        let _ = self.send(*progress);
+    }
+}
+
+/// A handle shared between a solve and the threads watching it. Cloning the handle shares the
+/// underlying state.
+#[derive(Clone, Default)]
+pub struct SolveHandle {
+    cancelled: Arc<AtomicBool>,
+    progress: Arc<Mutex<Option<SolveProgress>>>,
+}
+
+impl SolveHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests that the solve stops after its current step.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// The most recent progress reported by the solve.
+    pub fn progress(&self) -> Option<SolveProgress> {
+        *self.progress.lock().unwrap()
+    }
+
+    pub(super) fn report(&self, progress: &SolveProgress) {
+        *self.progress.lock().unwrap() = Some(*progress);
+    }
+}
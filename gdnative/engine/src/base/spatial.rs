@@ -0,0 +1,411 @@
+use glam::DVec3;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+const LEAF_SIZE: usize = 8;
+
+/// A node of the tree, bounding the points in `start..end` with a sphere.
+struct Node {
+    center: DVec3,
+    radius: f64,
+    start: usize,
+    end: usize,
+    children: Option<(usize, usize)>,
+}
+
+/// A kd-tree over points identified by an id, built once and queried many times. Each node is
+/// bounded by a sphere, which is all the nearest, radius and ray queries need to skip it. Ids
+/// break ties between points at the same distance.
+pub struct SpatialIndex<I> {
+    points: Vec<(I, DVec3)>,
+    nodes: Vec<Node>,
+}
+
+/// A point hit by a ray.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit<I> {
+    pub id: I,
+    /// Distance from the origin of the ray to the point, along the ray
+    pub along: f64,
+    /// Distance from the point to the ray
+    pub distance: f64,
+}
+
+/// How far a point may be from a ray and still be hit. The angular part widens the tolerance with
+/// the distance along the ray, which gives a constant tolerance on screen for a perspective
+/// camera at the origin of the ray.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayTolerance {
+    pub world: f64,
+    pub angular: f64,
+}
+
+impl RayTolerance {
+    fn at(&self, along: f64) -> f64 {
+        self.world + self.angular * along.max(0.0)
+    }
+}
+
+impl<I: Copy + Ord> SpatialIndex<I> {
+    pub fn new(points: Vec<(I, DVec3)>) -> Self {
+        let mut index = Self {
+            points,
+            nodes: Vec::new(),
+        };
+
+        if !index.points.is_empty() {
+            index.build(0, index.points.len());
+        }
+
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    fn build(&mut self, start: usize, end: usize) -> usize {
+        let points = &mut self.points[start..end];
+
+        let (min, max) = points.iter().fold(
+            (DVec3::splat(f64::INFINITY), DVec3::splat(f64::NEG_INFINITY)),
+            |(min, max), (_id, pos)| (min.min(*pos), max.max(*pos)),
+        );
+        let center = (min + max) * 0.5;
+        let radius = points
+            .iter()
+            .map(|(_id, pos)| pos.distance(center))
+            .fold(0.0, f64::max);
+
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            center,
+            radius,
+            start,
+            end,
+            children: None,
+        });
+
+        if end - start > LEAF_SIZE {
+            // Split at the median of the widest axis
+            let size = max - min;
+            let axis = if size.x >= size.y && size.x >= size.z {
+                0
+            } else if size.y >= size.z {
+                1
+            } else {
+                2
+            };
+
+            let mid = (end - start) / 2;
+            self.points[start..end]
+                .select_nth_unstable_by(mid, |a, b| a.1[axis].total_cmp(&b.1[axis]));
+
+            let left = self.build(start, start + mid);
+            let right = self.build(start + mid, end);
+            self.nodes[node].children = Some((left, right));
+        }
+
+        node
+    }
+
+    /// The `k` points closest to `point`, ordered by increasing distance.
+    pub fn nearest(&self, point: DVec3, k: usize) -> Vec<(I, f64)> {
+        if k == 0 || self.nodes.is_empty() {
+            return Vec::new();
+        }
+
+        // Nodes to visit, closest bound first, and the best points found so far, furthest first
+        let mut pending = BinaryHeap::new();
+        let mut best: BinaryHeap<Candidate<I>> = BinaryHeap::new();
+
+        pending.push(Pending(self.bound(0, point), 0));
+
+        while let Some(Pending(bound, node)) = pending.pop() {
+            if best.len() == k && bound >= best.peek().map_or(f64::INFINITY, |c| c.distance) {
+                break;
+            }
+
+            let node = &self.nodes[node];
+            match node.children {
+                Some((left, right)) => {
+                    pending.push(Pending(self.bound(left, point), left));
+                    pending.push(Pending(self.bound(right, point), right));
+                }
+                None => {
+                    for &(id, pos) in &self.points[node.start..node.end] {
+                        best.push(Candidate {
+                            distance: pos.distance(point),
+                            id,
+                        });
+                        if best.len() > k {
+                            best.pop();
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut nearest = best
+            .into_iter()
+            .map(|c| (c.id, c.distance))
+            .collect::<Vec<_>>();
+        nearest.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        nearest
+    }
+
+    /// Every point within `radius` of `point`, ordered by increasing distance.
+    pub fn within(&self, point: DVec3, radius: f64) -> Vec<(I, f64)> {
+        let mut found = Vec::new();
+        let mut stack = Vec::new();
+
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(node) = stack.pop() {
+            if self.bound(node, point) > radius {
+                continue;
+            }
+
+            let node = &self.nodes[node];
+            match node.children {
+                Some((left, right)) => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+                None => {
+                    for &(id, pos) in &self.points[node.start..node.end] {
+                        let distance = pos.distance(point);
+                        if distance <= radius {
+                            found.push((id, distance));
+                        }
+                    }
+                }
+            }
+        }
+
+        found.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        found
+    }
+
+    /// The point in front of the ray which is closest to it relative to the tolerance at its
+    /// distance, which for an angular tolerance is the point closest to the ray on screen.
+    pub fn ray(
+        &self,
+        origin: DVec3,
+        direction: DVec3,
+        tolerance: RayTolerance,
+    ) -> Option<RayHit<I>> {
+        let direction = direction.try_normalize()?;
+
+        let mut best: Option<(f64, RayHit<I>)> = None;
+        let mut stack = Vec::new();
+
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+
+            // Points in the sphere are at least this far from the ray, and no further along it
+            // than the end of the sphere
+            let along = (node.center - origin).dot(direction).max(0.0);
+            let distance = (origin + direction * along).distance(node.center) - node.radius;
+            if distance > tolerance.at(along + node.radius) {
+                continue;
+            }
+
+            match node.children {
+                Some((left, right)) => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+                None => {
+                    for &(id, pos) in &self.points[node.start..node.end] {
+                        let along = (pos - origin).dot(direction);
+                        if along < 0.0 {
+                            continue;
+                        }
+
+                        let distance = (origin + direction * along).distance(pos);
+                        let allowed = tolerance.at(along);
+                        if distance > allowed {
+                            continue;
+                        }
+
+                        let score = if allowed > 0.0 {
+                            distance / allowed
+                        } else {
+                            0.0
+                        };
+                        let hit = RayHit {
+                            id,
+                            along,
+                            distance,
+                        };
+
+                        let better = match best {
+                            Some((best_score, best_hit)) => {
+                                score < best_score
+                                    || (score == best_score && along < best_hit.along)
+                            }
+                            None => true,
+                        };
+                        if better {
+                            best = Some((score, hit));
+                        }
+                    }
+                }
+            }
+        }
+
+        best.map(|(_score, hit)| hit)
+    }
+
+    /// Lower bound on the distance from `point` to anything in a node.
+    fn bound(&self, node: usize, point: DVec3) -> f64 {
+        let node = &self.nodes[node];
+        (node.center.distance(point) - node.radius).max(0.0)
+    }
+}
+
+/// A node waiting to be visited by `nearest`, ordered so the closest bound pops first.
+struct Pending(f64, usize);
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0)
+    }
+}
+
+/// A point found by `nearest`, ordered so the furthest pops first.
+struct Candidate<I> {
+    distance: f64,
+    id: I,
+}
+
+impl<I: Ord> PartialEq for Candidate<I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<I: Ord> Eq for Candidate<I> {}
+
+impl<I: Ord> PartialOrd for Candidate<I> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<I: Ord> Ord for Candidate<I> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.id.cmp(&other.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_match_brute_force() {
+        // Points on a scrambled lattice, so distances are distinct enough to compare orderings
+        let points = (0..500)
+            .map(|i| {
+                let x = ((i * 37) % 101) as f64;
+                let y = ((i * 53) % 89) as f64;
+                let z = ((i * 71) % 97) as f64;
+                (i, DVec3::new(x, y, z * 0.5))
+            })
+            .collect::<Vec<_>>();
+
+        let index = SpatialIndex::new(points.clone());
+        let query = DVec3::new(40.3, 20.7, 11.1);
+
+        let mut brute = points
+            .iter()
+            .map(|(id, pos)| (*id, pos.distance(query)))
+            .collect::<Vec<_>>();
+        brute.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+
+        assert_eq!(index.nearest(query, 7), brute[..7].to_vec());
+
+        let within = brute
+            .iter()
+            .copied()
+            .filter(|(_id, d)| *d <= 15.0)
+            .collect::<Vec<_>>();
+        assert_eq!(index.within(query, 15.0), within);
+
+        let (id, pos) = points[123];
+        let origin = pos + DVec3::new(-200.0, 0.05, 0.0);
+        let hit = index
+            .ray(
+                origin,
+                DVec3::X,
+                RayTolerance {
+                    world: 0.1,
+                    angular: 0.0,
+                },
+            )
+            .unwrap();
+        assert_eq!(hit.id, id);
+        assert!((hit.along - 200.0).abs() < 1e-9);
+
+        assert!(index
+            .ray(
+                origin,
+                -DVec3::X,
+                RayTolerance {
+                    world: 0.1,
+                    angular: 0.0,
+                },
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn points_with_compound_ids() {
+        // The same index in two groups, as bodies are in two subsystems
+        let index = SpatialIndex::new(vec![
+            ((0, 0), DVec3::new(1.0, 0.0, 0.0)),
+            ((1, 0), DVec3::new(-1.0, 0.0, 0.0)),
+            ((1, 1), DVec3::new(5.0, 0.0, 0.0)),
+        ]);
+
+        assert_eq!(
+            index.within(DVec3::ZERO, 2.0),
+            vec![((0, 0), 1.0), ((1, 0), 1.0)]
+        );
+
+        let tolerance = RayTolerance {
+            world: 0.1,
+            angular: 0.0,
+        };
+        let hit = index.ray(DVec3::new(-3.0, 0.0, 0.0), DVec3::X, tolerance);
+        assert_eq!(hit.map(|hit| hit.id), Some((1, 0)));
+    }
+}
@@ -1,16 +1,89 @@
+use super::edit::{Command, EditError, EditHistory};
+use super::schedule::{Event, Schedule};
+use super::solve::{SolveHandle, SolveObserver, SolveOutcome, SolveProgress};
 use super::{Root, System, SystemNode};
 use hashbrown::{hash_map::DefaultHashBuilder, HashMap};
+use serde::de::{DeserializeOwned, SeqAccess, Visitor};
+use serde::ser::SerializeSeq;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::any::{Any, TypeId};
+use std::fs::{self, File};
 use std::hash::{BuildHasher, BuildHasherDefault, Hasher};
+use std::io::{self, BufReader, BufWriter, Write};
 use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+use std::time::Instant;
+use thiserror::Error;
 
 pub trait Config: Any + Send + Sync {}
 
+#[derive(Debug, Error)]
+pub enum CheckpointError {
+    #[error("Failed to access checkpoint file")]
+    FileSystemError(#[from] io::Error),
+    #[error("Failed to (de)serialize checkpoint")]
+    SerializationError(#[from] bincode::Error),
+    #[error("Checkpoint does not contain an unfinished solve")]
+    NotSolving,
+}
+
+#[derive(Debug, Error)]
+pub enum SolveError {
+    #[error("Tree has not been solved yet")]
+    NotSolved,
+    #[error("A solve is already in progress")]
+    AlreadySolving,
+    #[error("Solving backwards would pass scheduled events, which only apply forwards in time")]
+    BackwardsThroughEvents,
+}
+
+/// The state of a solve which is in progress. It is stored with the tree, so a checkpoint taken
+/// mid-solve contains everything needed to finish it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SolveState {
+    pub start: f64,
+    pub end: f64,
+    pub delta: f64,
+    pub time: f64,
+    pub step: usize,
+    pub steps: usize,
+}
+
+impl SolveState {
+    pub fn is_finished(&self) -> bool {
+        self.step >= self.steps
+    }
+}
+
+/// A config which is written to and read from saved files. The key identifies the type in those
+/// files, so it must never change once files have been written with it.
+pub trait PersistentConfig: Config + Serialize + DeserializeOwned {
+    const KEY: &'static str;
+}
+
 #[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct SystemTree<R: System + Root> {
     root: SystemNode<R>,
     config: SystemConfigWrapper<R>,
+    /// Time range covered by the records of the tree
+    span: Option<(f64, f64)>,
+    /// Time the current state of the systems corresponds to, which is the start of the span if
+    /// the last solve went backwards in time
+    solved_to: Option<f64>,
+    solving: Option<SolveState>,
+    /// Persistent configs as they were before the current solve, as events only replace configs
+    /// until the solve ends
+    config_before: Option<Vec<u8>>,
+    schedule: Schedule<R::Event>,
+    /// Set once the tree has been edited since its records were solved
+    stale: bool,
+    #[serde(skip)]
+    history: EditHistory<R>,
+    /// Time being viewed, while a view is in progress
+    #[serde(skip)]
+    viewing: Option<f64>,
 }
 
 impl<R: System + Root> SystemTree<R> {
@@ -18,21 +91,473 @@ impl<R: System + Root> SystemTree<R> {
         Self {
             root: SystemNode::new(root),
             config: SystemConfigWrapper(R::default_config(), PhantomData),
+            span: None,
+            solved_to: None,
+            solving: None,
+            config_before: None,
+            schedule: Schedule::new(),
+            stale: false,
+            history: EditHistory::default(),
+            viewing: None,
         }
     }
 
     pub fn solve(&mut self, start: f64, end: f64, iterations: usize) {
+        self.solve_begin(start, end, iterations);
+        while self.solve_step() {}
+        self.solve_end();
+    }
+
+    /// Solves the tree, reporting progress to `observer` after every step. The solve stops early
+    /// if `handle` is cancelled, in which case the tree is left viewable up to the last completed
+    /// step.
+    pub fn solve_observed(
+        &mut self,
+        start: f64,
+        end: f64,
+        iterations: usize,
+        handle: &SolveHandle,
+        observer: &mut dyn SolveObserver,
+    ) -> SolveOutcome {
+        let began = Instant::now();
+
+        self.solve_begin(start, end, iterations);
+
+        let mut outcome = SolveOutcome::Completed;
+
+        while !handle.is_cancelled() && self.solve_step() {
+            let progress = SolveProgress::new(self.solving.as_ref().unwrap(), began);
+
+            handle.report(&progress);
+            observer.on_progress(&progress);
+        }
+
+        if let Some(state) = self.solving.as_ref() {
+            if !state.is_finished() {
+                outcome = SolveOutcome::Cancelled(state.time);
+            }
+        }
+
+        self.solve_end();
+
+        outcome
+    }
+
+    /// Continues a solved tree from where the last solve ended to a new end time, which may be
+    /// earlier than the last end time to solve backwards.
+    pub fn extend(&mut self, end: f64, iterations: usize) -> Result<(), SolveError> {
+        let start = self.solved_to.ok_or(SolveError::NotSolved)?;
+
+        self.check_solve(start, end)?;
+        self.solve(start, end, iterations);
+
+        Ok(())
+    }
+
+    /// Checks that a solve from `start` to `end` can begin. Scheduled events are only applied
+    /// when solving forwards, so a backward solve over any of them is rejected rather than
+    /// silently skipping them.
+    pub fn check_solve(&self, start: f64, end: f64) -> Result<(), SolveError> {
+        if self.solving.is_some() {
+            Err(SolveError::AlreadySolving)
+        } else if end < start && !self.schedule.between(end, start).is_empty() {
+            Err(SolveError::BackwardsThroughEvents)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Solves the tree, writing a checkpoint to `path` every `every` steps. If the process dies
+    /// mid-solve, `resume` finishes the solve from the last checkpoint.
+    pub fn solve_checkpointed(
+        &mut self,
+        start: f64,
+        end: f64,
+        iterations: usize,
+        path: &Path,
+        every: usize,
+    ) -> Result<(), CheckpointError> {
+        self.solve_begin(start, end, iterations);
+        self.solve_checkpointed_remaining(path, every)
+    }
+
+    /// Loads the checkpoint at `path` and finishes its solve, continuing to write checkpoints.
+    pub fn resume(path: &Path, every: usize) -> Result<Self, CheckpointError> {
+        let mut tree = Self::load_checkpoint(path)?;
+
+        if tree.solving.is_none() {
+            return Err(CheckpointError::NotSolving);
+        }
+
+        tree.solve_checkpointed_remaining(path, every)?;
+
+        Ok(tree)
+    }
+
+    fn solve_checkpointed_remaining(
+        &mut self,
+        path: &Path,
+        every: usize,
+    ) -> Result<(), CheckpointError> {
+        let every = every.max(1);
+
+        while self.solve_step() {
+            if self.solving.is_some_and(|state| state.step % every == 0) {
+                self.checkpoint(path)?;
+            }
+        }
+
+        self.solve_end();
+
+        // The solve completed, so the checkpoint is no longer needed.
+        match fs::remove_file(path) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Begins a solve, which is advanced by `solve_step` and completed with `solve_end`. A solve
+    /// starting where the last one ended continues it. Any other solve starts over from the
+    /// initial conditions, discarding the records of earlier solves. Use `check_solve` first to
+    /// reject solves which can't apply the schedule.
+    pub fn solve_begin(&mut self, start: f64, end: f64, iterations: usize) {
+        self.view_end();
+
+        let continues = !self.stale && self.solved_to == Some(start);
+
+        if !continues {
+            self.root.reset(&self.config.0);
+            self.root.invalidate(&self.config.0);
+            self.span = None;
+            self.solved_to = None;
+        }
+
+        self.config_before = bincode::serialize(&self.config.0).ok();
+
+        // Configs replaced by events which the continued solves went through stay replaced
+        if continues {
+            if let Some((first, _last)) = self.span {
+                for scheduled in self.schedule.between(first, start) {
+                    if let Event::Config { key, bytes } = &scheduled.event {
+                        apply_config_event(&mut self.config.0, key, bytes);
+                    }
+                }
+            }
+        }
+
         self.root.solve_begin(&self.config.0, start);
 
-        let mut time = start;
-        let delta = (end - start) / (iterations + 1) as f64;
+        self.solving = Some(SolveState {
+            start,
+            end,
+            delta: (end - start) / (iterations + 1) as f64,
+            time: start,
+            step: 0,
+            steps: iterations + 1,
+        });
+    }
+
+    /// Advances the current solve by a single step. Returns false once every step has been taken.
+    /// Scheduled events which fall within the step split it, so each is applied at its exact time.
+    /// Events are only applied when solving forwards in time, see `check_solve`.
+    pub fn solve_step(&mut self) -> bool {
+        let state = match self.solving.as_mut() {
+            Some(state) if !state.is_finished() => state,
+            _ => return false,
+        };
+
+        // Step times are computed from the start so rounding errors don't accumulate, and the
+        // last step lands exactly on the end time.
+        let target = if state.step + 1 >= state.steps {
+            state.end
+        } else {
+            state.start + state.delta * (state.step + 1) as f64
+        };
+        let mut time = state.time;
+
+        for scheduled in self.schedule.between(state.time, target) {
+            if scheduled.time > time {
+                self.root
+                    .solve_update(&self.config.0, time, scheduled.time - time);
+                time = scheduled.time;
+            }
+
+            match &scheduled.event {
+                Event::System(event) => R::apply_event(&mut self.root, &self.config.0, time, event),
+                Event::Config { key, bytes } => apply_config_event(&mut self.config.0, key, bytes),
+            }
+        }
+
+        if target != time {
+            self.root.solve_update(&self.config.0, time, target - time);
+        }
+
+        state.time = target;
+        state.step += 1;
+
+        true
+    }
+
+    /// Ends the current solve at the time reached by the last completed step.
+    pub fn solve_end(&mut self) {
+        if let Some(state) = self.solving.take() {
+            self.root.solve_end(&self.config.0, state.time);
+
+            if let Some(bytes) = self.config_before.take() {
+                restore_configs(&mut self.config.0, &bytes);
+            }
+
+            let low = state.start.min(state.time);
+            let high = state.start.max(state.time);
+
+            self.span = match self.span {
+                Some((start, end)) if self.solved_to == Some(state.start) => {
+                    Some((start.min(low), end.max(high)))
+                }
+                _ => Some((low, high)),
+            };
+            self.solved_to = Some(state.time);
+
+            self.history.solved = Some(self.history.revision);
+            self.stale = false;
+        }
+    }
+
+    /// Applies a command to the tree. Outside of a transaction the command can be undone on its
+    /// own, otherwise it is undone with the rest of the transaction.
+    pub fn edit<C: Command<R> + 'static>(&mut self, command: C) -> Result<(), EditError> {
+        if self.solving.is_some() {
+            return Err(EditError::Solving);
+        }
+
+        let single = self.history.open.is_none();
+        if single {
+            self.history.begin()?;
+        }
+
+        let mut command: Box<dyn Command<R>> = Box::new(command);
+
+        if command.invalidates() {
+            self.reset_to_initial();
+        }
+
+        self.root.edit_begin(&self.config.0);
+        let result = command.apply(&mut self.root, &mut self.config.0);
+        self.end_edit();
+
+        if result.is_ok() {
+            if let Some(transaction) = self.history.open.as_mut() {
+                transaction.commands.push(command);
+            }
+        }
+
+        if single {
+            self.commit_transaction()?;
+        }
+
+        result
+    }
+
+    /// Groups the following edits so they are undone and redone together.
+    pub fn begin_transaction(&mut self) -> Result<(), EditError> {
+        self.history.begin()
+    }
+
+    pub fn commit_transaction(&mut self) -> Result<(), EditError> {
+        // The records were solved from the state before the first edit since they were loaded.
+        if !self.stale && self.history.solved.is_none() {
+            self.history.solved = Some(self.history.revision);
+        }
+
+        self.history.commit()?;
+        self.update_stale();
+
+        Ok(())
+    }
+
+    /// Undoes every edit of the open transaction and closes it.
+    pub fn rollback_transaction(&mut self) -> Result<(), EditError> {
+        let mut transaction = self.history.open.take().ok_or(EditError::NoTransaction)?;
+
+        self.root.edit_begin(&self.config.0);
+        for command in transaction.commands.iter_mut().rev() {
+            command.undo(&mut self.root, &mut self.config.0);
+        }
+        self.end_edit();
+
+        Ok(())
+    }
+
+    pub fn undo(&mut self) -> Result<(), EditError> {
+        self.check_history()?;
+
+        let mut transaction = self.history.undo.pop().ok_or(EditError::NothingToUndo)?;
+
+        if transaction.invalidates() {
+            self.reset_to_initial();
+        }
+
+        self.root.edit_begin(&self.config.0);
+        for command in transaction.commands.iter_mut().rev() {
+            command.undo(&mut self.root, &mut self.config.0);
+        }
+        self.end_edit();
+
+        self.history.revision = transaction.before;
+        self.history.redo.push(transaction);
+        self.update_stale();
+
+        Ok(())
+    }
+
+    pub fn redo(&mut self) -> Result<(), EditError> {
+        self.check_history()?;
+
+        let mut transaction = self.history.redo.pop().ok_or(EditError::NothingToRedo)?;
+
+        if transaction.invalidates() {
+            self.reset_to_initial();
+        }
+
+        self.root.edit_begin(&self.config.0);
+
+        let mut result = Ok(());
+        for (i, command) in transaction.commands.iter_mut().enumerate() {
+            result = command.apply(&mut self.root, &mut self.config.0);
+
+            if result.is_err() {
+                // Leave the tree as it was before the redo
+                for command in transaction.commands[..i].iter_mut().rev() {
+                    command.undo(&mut self.root, &mut self.config.0);
+                }
+                break;
+            }
+        }
+
+        self.end_edit();
+
+        if result.is_err() {
+            self.history.redo.push(transaction);
+            return result;
+        }
+
+        self.history.revision = transaction.after;
+        self.history.undo.push(transaction);
+        self.update_stale();
+
+        Ok(())
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    /// Whether the tree has been edited since it was solved, so its records no longer match its
+    /// initial conditions and it has to be solved again.
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Writes the state of the tree at `time` into the components of its systems, beginning a
+    /// view if one is not already in progress.
+    pub fn view(&mut self, time: f64) {
+        match self.viewing {
+            Some(_) => self.root.view_set_time(&self.config.0, time),
+            None => self.root.view_begin(&self.config.0, time),
+        }
+
+        self.viewing = Some(time);
+    }
+
+    pub fn view_end(&mut self) {
+        if let Some(time) = self.viewing.take() {
+            self.root.view_end(&self.config.0, time);
+        }
+    }
+
+    pub fn viewing(&self) -> Option<f64> {
+        self.viewing
+    }
+
+    /// Returns the systems to the initial conditions of the last solve, so edits change those
+    /// rather than the state the solve ended in. The records are kept, but the solve can no longer
+    /// be extended.
+    fn reset_to_initial(&mut self) {
+        if self.solved_to.take().is_some() {
+            self.root.reset(&self.config.0);
+        }
+    }
+
+    /// Ends an edit, refreshing the view so it includes anything the edit added.
+    fn end_edit(&mut self) {
+        self.root.edit_end(&self.config.0);
+
+        if let Some(time) = self.viewing {
+            self.root.view_begin(&self.config.0, time);
+        }
+    }
+
+    fn check_history(&self) -> Result<(), EditError> {
+        if self.solving.is_some() {
+            Err(EditError::Solving)
+        } else if self.history.open.is_some() {
+            Err(EditError::TransactionOpen)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn update_stale(&mut self) {
+        self.stale = self.span.is_some() && self.history.solved != Some(self.history.revision);
+    }
+
+    /// Events applied during solves. Changing the schedule only affects later solves.
+    pub fn schedule(&self) -> &Schedule<R::Event> {
+        &self.schedule
+    }
+
+    pub fn schedule_mut(&mut self) -> &mut Schedule<R::Event> {
+        &mut self.schedule
+    }
+
+    pub fn solving(&self) -> Option<&SolveState> {
+        self.solving.as_ref()
+    }
+
+    /// The time range covered by the records of the tree.
+    pub fn span(&self) -> Option<(f64, f64)> {
+        self.span
+    }
+
+    /// The time the current state of the tree corresponds to, and where `extend` continues from.
+    pub fn solved_to(&self) -> Option<f64> {
+        self.solved_to
+    }
+
+    /// Writes the tree, including any solve in progress, to `path`. The previous checkpoint is only
+    /// replaced once the new one has been completely written.
+    pub fn checkpoint(&self, path: &Path) -> Result<(), CheckpointError> {
+        let temp = path.with_extension("tmp");
 
-        for _i in 0..(iterations + 1) {
-            self.root.solve_update(&self.config.0, time, delta);
-            time += delta;
+        {
+            let mut writer = BufWriter::new(File::create(&temp)?);
+            bincode::serialize_into(&mut writer, self)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
         }
 
-        self.root.solve_end(&self.config.0, time);
+        fs::rename(&temp, path)?;
+
+        Ok(())
+    }
+
+    pub fn load_checkpoint(path: &Path) -> Result<Self, CheckpointError> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(bincode::deserialize_from(reader)?)
     }
 
     pub fn root(&self) -> &SystemNode<R> {
@@ -68,10 +593,11 @@ impl<'de, R: Root> Deserialize<'de> for SystemConfigWrapper<R> {
     where
         D: Deserializer<'de>,
     {
-        Ok(SystemConfigWrapper(
-            R::deserialize_config(deserializer)?,
-            PhantomData,
-        ))
+        // Configs missing from the file keep their default values.
+        let mut config = R::default_config();
+        config.extend(R::deserialize_config(deserializer)?);
+
+        Ok(SystemConfigWrapper(config, PhantomData))
     }
 }
 
@@ -100,7 +626,7 @@ impl SystemConfig {
         self.configs
             .get(&TypeId::of::<T>())
             .map(|v| {
-                let s: &dyn Any = v;
+                let s: &dyn Any = v.as_ref();
                 s.downcast_ref::<T>()
             })
             .flatten()
@@ -110,11 +636,188 @@ impl SystemConfig {
         self.configs
             .get_mut(&TypeId::of::<T>())
             .map(|v| {
-                let s: &mut dyn Any = v;
+                let s: &mut dyn Any = v.as_mut();
                 s.downcast_mut::<T>()
             })
             .flatten()
     }
+
+    /// Moves every config of `other` into this one, replacing configs of the same type.
+    pub fn extend(&mut self, other: SystemConfig) {
+        self.configs.extend(other.configs);
+    }
+}
+
+/// Configs are stored as a sequence of `(key, bytes)` pairs. Configs which have not been
+/// registered as persistent are not saved.
+impl Serialize for SystemConfig {
+    fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+    where
+        Ser: Serializer,
+    {
+        use serde::ser::Error;
+
+        let registry = config_registry().read().unwrap();
+
+        let mut entries = Vec::new();
+
+        for (type_id, config) in self.configs.iter() {
+            if let Some(entry) = registry.by_type.get(type_id) {
+                let bytes = (entry.serialize)(config.as_ref()).map_err(Ser::Error::custom)?;
+                entries.push((entry.key, bytes));
+            }
+        }
+
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut seq = serializer.serialize_seq(Some(entries.len()))?;
+        for entry in entries.iter() {
+            seq.serialize_element(entry)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for SystemConfig {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ConfigVisitor;
+
+        impl<'de> Visitor<'de> for ConfigVisitor {
+            type Value = SystemConfig;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("SystemConfig sequence.")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                use serde::de::Error;
+
+                let mut config = SystemConfig::new();
+
+                while let Some((key, bytes)) = seq.next_element::<(String, Vec<u8>)>()? {
+                    // The registry is not held while deserializing, which may need it again.
+                    let deserialize = config_registry()
+                        .read()
+                        .unwrap()
+                        .deserializer(&key)
+                        .ok_or_else(|| {
+                            A::Error::custom(format!("unregistered config type `{}`", key))
+                        })?;
+
+                    deserialize(&bytes, &mut config).map_err(A::Error::custom)?;
+                }
+
+                Ok(config)
+            }
+        }
+
+        deserializer.deserialize_seq(ConfigVisitor)
+    }
+}
+
+/// Replaces the config stored under `key` with the one in `bytes`.
+fn apply_config_event(config: &mut SystemConfig, key: &str, bytes: &[u8]) {
+    // Events can only be created for registered configs, and a schedule with unknown keys fails
+    // to load, so the key is always present.
+    let deserialize = config_registry().read().unwrap().deserializer(key);
+    if let Some(deserialize) = deserialize {
+        let _ = deserialize(bytes, config);
+    }
+}
+
+/// Puts back the persistent configs saved in `bytes`, removing those which have been added since.
+fn restore_configs(config: &mut SystemConfig, bytes: &[u8]) {
+    let before = match bincode::deserialize::<SystemConfig>(bytes) {
+        Ok(before) => before,
+        Err(_) => return,
+    };
+
+    {
+        let registry = config_registry().read().unwrap();
+        config
+            .configs
+            .retain(|type_id, _config| !registry.by_type.contains_key(type_id));
+    }
+
+    config.extend(before);
+}
+
+type ConfigDeserializer = fn(&[u8], &mut SystemConfig) -> Result<(), bincode::Error>;
+
+struct ConfigEntry {
+    key: &'static str,
+    serialize: fn(&dyn Config) -> Result<Vec<u8>, bincode::Error>,
+}
+
+/// Maps persistent configs to and from their keys.
+pub struct ConfigRegistry {
+    by_type: TypeIdMap<ConfigEntry>,
+    by_key: HashMap<&'static str, ConfigDeserializer>,
+}
+
+impl ConfigRegistry {
+    fn new() -> Self {
+        Self {
+            by_type: TypeIdMap::default(),
+            by_key: HashMap::new(),
+        }
+    }
+
+    pub fn register<C: PersistentConfig>(&mut self) {
+        fn serialize<C: PersistentConfig>(config: &dyn Config) -> Result<Vec<u8>, bincode::Error> {
+            let config: &dyn Any = config;
+            // The registry only calls this for configs stored under the type id of `C`.
+            bincode::serialize(config.downcast_ref::<C>().unwrap())
+        }
+
+        fn deserialize<C: PersistentConfig>(
+            bytes: &[u8],
+            config: &mut SystemConfig,
+        ) -> Result<(), bincode::Error> {
+            config.insert(bincode::deserialize::<C>(bytes)?);
+            Ok(())
+        }
+
+        self.by_type.insert(
+            TypeId::of::<C>(),
+            ConfigEntry {
+                key: C::KEY,
+                serialize: serialize::<C>,
+            },
+        );
+        self.by_key.insert(C::KEY, deserialize::<C>);
+    }
+
+    pub fn is_registered(&self, key: &str) -> bool {
+        self.by_key.contains_key(key)
+    }
+
+    fn deserializer(&self, key: &str) -> Option<ConfigDeserializer> {
+        self.by_key.get(key).copied()
+    }
+}
+
+/// The global registry used when saving and loading configs. Configs built into the engine are
+/// registered on first access.
+pub fn config_registry() -> &'static RwLock<ConfigRegistry> {
+    static REGISTRY: OnceLock<RwLock<ConfigRegistry>> = OnceLock::new();
+
+    REGISTRY.get_or_init(|| {
+        let mut registry = ConfigRegistry::new();
+        crate::global::register_configs(&mut registry);
+        crate::gravity::register_configs(&mut registry);
+        RwLock::new(registry)
+    })
+}
+
+pub fn register_config<C: PersistentConfig>() {
+    config_registry().write().unwrap().register::<C>();
 }
 
 /// A hasher optimized for hashing a single TypeId.
@@ -155,3 +858,409 @@ impl Hasher for TypeIdHasher {
 }
 
 type TypeIdMap<V> = HashMap<TypeId, V, BuildHasherDefault<TypeIdHasher>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Softening {
+        length: f64,
+    }
+
+    impl Config for Softening {}
+
+    impl PersistentConfig for Softening {
+        const KEY: &'static str = "test_softening";
+    }
+
+    struct Transient;
+
+    impl Config for Transient {}
+
+    #[test]
+    fn config_round_trip() {
+        register_config::<Softening>();
+
+        let mut config = SystemConfig::new();
+        config.insert(Softening { length: 0.25 });
+        config.insert(Transient);
+
+        let bytes = bincode::serialize(&config).unwrap();
+        let loaded: SystemConfig = bincode::deserialize(&bytes).unwrap();
+
+        assert_eq!(loaded.get::<Softening>(), Some(&Softening { length: 0.25 }));
+        assert!(loaded.get::<Transient>().is_none());
+    }
+
+    #[test]
+    fn unknown_config_key() {
+        let bytes = bincode::serialize(&vec![("test_missing", vec![0u8])]).unwrap();
+
+        assert!(bincode::deserialize::<SystemConfig>(&bytes).is_err());
+    }
+
+    mod solve {
+        use crate::base::{
+            ContinuousRecord, Event, SolveError, SolveHandle, SolveOutcome, SolveProgress,
+            Subsystem, SystemNode, SystemTree,
+        };
+        use crate::gravity::event::{Despawned, GravitationalEvent};
+        use crate::gravity::mesh::{MeshSettings, ParticleMesh};
+        use crate::gravity::nbody::{ForceBackend, NBody, NBodySystem, Position, ViewState};
+        use crate::gravity::GravitationalSystem;
+        use glam::DVec3;
+
+        fn two_body() -> SystemTree<GravitationalSystem> {
+            let mut tree = SystemTree::new(GravitationalSystem);
+            let mut nbodies = SystemNode::new(NBodySystem);
+
+            nbodies.children_mut().spawn((
+                NBody {
+                    index: 0,
+                    pos: DVec3::new(1.0, 0.0, 0.0),
+                    vel: DVec3::new(0.0, 0.5, 0.0),
+                    mass: 0.5,
+                },
+                ContinuousRecord::<Position>::new(),
+            ));
+
+            nbodies.children_mut().spawn((
+                NBody {
+                    index: 1,
+                    pos: DVec3::new(-1.0, 0.0, 0.0),
+                    vel: DVec3::new(0.0, -0.5, 0.0),
+                    mass: 0.5,
+                },
+                ContinuousRecord::<Position>::new(),
+            ));
+
+            tree.root_mut()
+                .children_mut()
+                .spawn((Subsystem::new(nbodies),));
+
+            tree
+        }
+
+        fn snapshot(tree: &SystemTree<GravitationalSystem>) -> Vec<u8> {
+            bincode::serialize(tree.root()).unwrap()
+        }
+
+        #[test]
+        fn resume_matches_uninterrupted() {
+            let path = std::env::temp_dir().join(format!(
+                "constellation-resume-{}.checkpoint",
+                std::process::id()
+            ));
+
+            let mut uninterrupted = two_body();
+            uninterrupted.solve(0.0, 5.0, 99);
+
+            // Simulate a crash part way through a checkpointed solve
+            let mut crashed = two_body();
+            crashed.solve_begin(0.0, 5.0, 99);
+            for _ in 0..37 {
+                crashed.solve_step();
+            }
+            crashed.checkpoint(&path).unwrap();
+            for _ in 0..5 {
+                crashed.solve_step();
+            }
+            drop(crashed);
+
+            let resumed = SystemTree::<GravitationalSystem>::resume(&path, 10).unwrap();
+
+            assert!(!path.exists());
+            assert_eq!(resumed.span(), uninterrupted.span());
+            assert_eq!(snapshot(&resumed), snapshot(&uninterrupted));
+        }
+
+        #[test]
+        fn cancel_observed_solve() {
+            let mut tree = two_body();
+            let handle = SolveHandle::new();
+            let canceller = handle.clone();
+
+            let mut reports = Vec::new();
+            let outcome =
+                tree.solve_observed(0.0, 10.0, 99, &handle, &mut |progress: &SolveProgress| {
+                    reports.push(progress.step);
+                    if progress.step == 25 {
+                        canceller.cancel();
+                    }
+                });
+
+            let (start, end) = tree.span().unwrap();
+
+            assert_eq!(reports, (1..=25).collect::<Vec<_>>());
+            assert_eq!(outcome, SolveOutcome::Cancelled(end));
+            assert_eq!(start, 0.0);
+            assert!((end - 2.5).abs() < 1.0e-12);
+            assert!(tree.solving().is_none());
+            assert_eq!(handle.progress().unwrap().step, 25);
+        }
+
+        #[test]
+        fn extend_appends_records() {
+            let mut tree = two_body();
+
+            assert!(tree.extend(2.0, 9).is_err());
+
+            tree.solve(0.0, 1.0, 9);
+            tree.extend(2.0, 9).unwrap();
+
+            let (start, end) = tree.span().unwrap();
+            assert_eq!(start, 0.0);
+            assert!((end - 2.0).abs() < 1.0e-12);
+
+            for (_e, subsystem) in tree.root().children().query::<&Subsystem>().iter() {
+                let nbody = subsystem.downcast_ref::<NBodySystem>().unwrap();
+                for (_e, (body, record)) in nbody
+                    .children()
+                    .query::<(&NBody, &ContinuousRecord<Position>)>()
+                    .iter()
+                {
+                    let late = record.load(1.5).pos;
+                    assert!(
+                        late.length() > 0.0,
+                        "body {} has no record at t=1.5",
+                        body.index
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn scheduled_events_survive_reload() {
+            let mut tree = two_body();
+
+            let schedule = tree.schedule_mut();
+            schedule.insert(
+                0.55,
+                Event::System(GravitationalEvent::Impulse {
+                    index: 0,
+                    dv: DVec3::new(0.0, 0.1, 0.0),
+                }),
+            );
+            schedule.insert(0.8, Event::System(GravitationalEvent::Despawn { index: 1 }));
+            schedule.insert(
+                0.35,
+                Event::System(GravitationalEvent::Spawn(NBody {
+                    index: 2,
+                    pos: DVec3::new(0.0, 3.0, 0.0),
+                    vel: DVec3::ZERO,
+                    mass: 0.1,
+                })),
+            );
+
+            let bytes = bincode::serialize(&tree).unwrap();
+            let mut reloaded: SystemTree<GravitationalSystem> =
+                bincode::deserialize(&bytes).unwrap();
+
+            assert_eq!(reloaded.schedule().len(), 3);
+
+            tree.solve(0.0, 1.0, 9);
+            reloaded.solve(0.0, 1.0, 9);
+
+            assert_eq!(snapshot(&tree), snapshot(&reloaded));
+
+            for (_e, subsystem) in tree.root().children().query::<&Subsystem>().iter() {
+                let nbody = subsystem.downcast_ref::<NBodySystem>().unwrap();
+                for (_e, (body, record, despawned)) in nbody
+                    .children()
+                    .query::<(&NBody, &ContinuousRecord<Position>, Option<&Despawned>)>()
+                    .iter()
+                {
+                    match body.index {
+                        1 => assert_eq!(despawned.map(|despawned| despawned.time), Some(0.8)),
+                        2 => {
+                            assert_eq!(record.load(0.3).pos, DVec3::ZERO);
+                            assert!(record.load(0.35).pos.length() > 0.0);
+                        }
+                        _ => assert!(despawned.is_none()),
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn solving_twice_repeats_the_solve() {
+            let mut tree = two_body();
+
+            let mesh = ForceBackend::ParticleMesh(ParticleMesh::new(MeshSettings {
+                grid: 16,
+                ..MeshSettings::default()
+            }));
+
+            let schedule = tree.schedule_mut();
+            schedule.insert(
+                0.35,
+                Event::System(GravitationalEvent::Spawn(NBody {
+                    index: 2,
+                    pos: DVec3::new(0.0, 3.0, 0.0),
+                    vel: DVec3::ZERO,
+                    mass: 0.1,
+                })),
+            );
+            schedule.insert(0.5, Event::config(&mesh).unwrap());
+            schedule.insert(
+                0.55,
+                Event::System(GravitationalEvent::Impulse {
+                    index: 0,
+                    dv: DVec3::new(0.0, 0.1, 0.0),
+                }),
+            );
+            schedule.insert(0.8, Event::System(GravitationalEvent::Despawn { index: 1 }));
+
+            // Archetypes may be laid out differently, so the states are compared body by body
+            let states = |tree: &SystemTree<GravitationalSystem>| {
+                let mut states = Vec::new();
+                for (_e, subsystem) in tree.root().children().query::<&Subsystem>().iter() {
+                    let nbody = subsystem.downcast_ref::<NBodySystem>().unwrap();
+                    for (_e, (body, record, despawned)) in nbody
+                        .children()
+                        .query::<(&NBody, &ContinuousRecord<Position>, Option<&Despawned>)>()
+                        .iter()
+                    {
+                        let path = (0..=10)
+                            .map(|step| record.load(step as f64 * 0.1).pos)
+                            .collect::<Vec<_>>();
+                        states.push((
+                            body.index,
+                            body.pos,
+                            body.vel,
+                            body.mass,
+                            despawned.map(|despawned| despawned.time),
+                            path,
+                        ));
+                    }
+                }
+                states.sort_by_key(|state| state.0);
+                states
+            };
+
+            tree.solve(0.0, 1.0, 9);
+            let first = states(&tree);
+            assert_eq!(first.len(), 3);
+            assert!(tree.config().get::<ForceBackend>().is_none());
+
+            tree.solve(0.0, 1.0, 9);
+            assert_eq!(states(&tree), first);
+            assert!(tree.config().get::<ForceBackend>().is_none());
+        }
+
+        #[test]
+        fn view_writes_state() {
+            let mut tree = two_body();
+            tree.solve(0.0, 1.0, 9);
+
+            let views = |tree: &SystemTree<GravitationalSystem>| {
+                let mut views = Vec::new();
+                for (_e, subsystem) in tree.root().children().query::<&Subsystem>().iter() {
+                    let nbody = subsystem.downcast_ref::<NBodySystem>().unwrap();
+                    for (_e, (body, view)) in
+                        nbody.children().query::<(&NBody, &ViewState)>().iter()
+                    {
+                        views.push((body.clone(), view.clone()));
+                    }
+                }
+                views.sort_by_key(|(body, _view)| body.index);
+                views
+            };
+
+            tree.view(1.0);
+            for (body, view) in views(&tree) {
+                assert!(view.visible);
+                assert_eq!(view.pos, body.pos);
+                assert_eq!(view.vel, body.vel);
+            }
+
+            tree.view(0.0);
+            let starts = views(&tree)
+                .iter()
+                .map(|(_body, view)| view.pos)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                starts,
+                vec![DVec3::new(1.0, 0.0, 0.0), DVec3::new(-1.0, 0.0, 0.0)]
+            );
+
+            tree.view(2.0);
+            assert!(views(&tree).iter().all(|(_body, view)| !view.visible));
+
+            tree.view_end();
+            assert!(views(&tree).is_empty());
+        }
+
+        #[test]
+        fn backward_solves_reject_events() {
+            let mut tree = two_body();
+            tree.schedule_mut().insert(
+                0.5,
+                Event::System(GravitationalEvent::Impulse {
+                    index: 0,
+                    dv: DVec3::new(0.0, 0.1, 0.0),
+                }),
+            );
+
+            tree.solve(0.0, 1.0, 9);
+            assert!(matches!(
+                tree.extend(0.0, 9),
+                Err(SolveError::BackwardsThroughEvents)
+            ));
+            assert_eq!(tree.solved_to(), Some(1.0));
+
+            // Solves which stay clear of the events may still go backwards, and an event at the
+            // start of a backward solve has not been applied yet
+            assert!(tree.check_solve(0.6, 0.0).is_err());
+            assert!(tree.check_solve(0.5, 0.0).is_ok());
+            assert!(tree.check_solve(3.0, 1.0).is_ok());
+        }
+
+        #[test]
+        fn forward_then_backward() {
+            let initial = two_body();
+            let mut tree = two_body();
+
+            tree.solve(0.0, 5.0, 499);
+            tree.extend(0.0, 499).unwrap();
+
+            assert_eq!(tree.span(), Some((0.0, 5.0)));
+            assert_eq!(tree.solved_to(), Some(0.0));
+
+            let bodies = |tree: &SystemTree<GravitationalSystem>| {
+                let mut bodies = Vec::new();
+                for (_e, subsystem) in tree.root().children().query::<&Subsystem>().iter() {
+                    let nbody = subsystem.downcast_ref::<NBodySystem>().unwrap();
+                    for (_e, body) in nbody.children().query::<&NBody>().iter() {
+                        bodies.push(body.clone());
+                    }
+                }
+                bodies.sort_by_key(|body| body.index);
+                bodies
+            };
+
+            for (start, end) in bodies(&initial).iter().zip(bodies(&tree).iter()) {
+                assert!((start.pos - end.pos).length() < 1.0e-9);
+                assert!((start.vel - end.vel).length() < 1.0e-9);
+            }
+
+            // Going further back than the forward solve prepends records
+            tree.extend(-1.0, 99).unwrap();
+            assert_eq!(tree.span(), Some((-1.0, 5.0)));
+
+            for (_e, subsystem) in tree.root().children().query::<&Subsystem>().iter() {
+                let nbody = subsystem.downcast_ref::<NBodySystem>().unwrap();
+                for (_e, record) in nbody
+                    .children()
+                    .query::<&ContinuousRecord<Position>>()
+                    .iter()
+                {
+                    assert_eq!(record.span(), Some((-1.0, 5.0)));
+                    assert!(record.load(-0.5).pos.length() > 0.0);
+                    assert!(record.load(2.5).pos.length() > 0.0);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::base::SystemRegistry;
+use engine_sys::error::EngineError;
+use engine_sys::generic::particle::{Particle, ParticleSolver, ParticleSolverSettings};
+use thiserror::Error;
+
+pub mod relativistic;
+
+pub use relativistic::{FieldSettings, FieldSystem};
+
+#[derive(Debug, Error)]
+pub enum FieldError {
+    #[error("Field solver is not available, the engine was built without the `native` feature")]
+    BackendUnavailable,
+    #[error("Field solver failed: {0}")]
+    Engine(EngineError),
+}
+
+impl From<EngineError> for FieldError {
+    fn from(error: EngineError) -> Self {
+        match error {
+            EngineError::BackendUnavailable => Self::BackendUnavailable,
+            error => Self::Engine(error),
+        }
+    }
+}
+
+/// Whether the engine was built with a field solver, either the native one or the mock.
+pub fn backend_available() -> bool {
+    cfg!(any(feature = "native", feature = "mock"))
+}
+
+/// Creates a field solver over `particles`, failing with `BackendUnavailable` when the engine was
+/// built without one.
+pub fn create_solver(
+    settings: &ParticleSolverSettings,
+    particles: &[Particle],
+) -> Result<ParticleSolver, FieldError> {
+    Ok(ParticleSolver::new(settings, particles)?)
+}
+
+/// Registers the systems of this module so they can be loaded as subsystems.
+pub fn register_systems(registry: &mut SystemRegistry) {
+    registry.register::<FieldSystem>();
+}
+
+#[cfg(all(test, not(any(feature = "native", feature = "mock"))))]
+mod tests {
+    use super::*;
+    use engine_sys::constants::Constants;
+
+    #[test]
+    fn unavailable_without_native() {
+        let settings = ParticleSolverSettings {
+            constants: Constants {
+                gravitational: 1.0,
+                speed_of_light: 1.0,
+            },
+            element_order: 1,
+            domain_size: [1.0; 3],
+            domain_refinement: 1,
+        };
+
+        assert!(!backend_available());
+        assert!(matches!(
+            create_solver(&settings, &[Particle::default()]),
+            Err(FieldError::BackendUnavailable)
+        ));
+    }
+}
@@ -0,0 +1,372 @@
+use super::{create_solver, FieldError};
+use crate::base::{Inspected, RegisteredSystem, System, SystemConfig};
+use crate::gravity::blackhole::Absorbed;
+use crate::gravity::event::Despawned;
+use crate::gravity::nbody::{self, NBody, NBodySystem, PhysicalConstants};
+use engine_sys::constants::Constants;
+use engine_sys::generic::particle::{Particle, ParticleSolver, ParticleSolverSettings};
+use glam::DVec3;
+use hecs::{Entity, World};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// How the spacetime around the bodies is discretised.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FieldSettings {
+    pub element_order: i32,
+    /// Half extents of the simulated domain
+    pub domain_size: [f64; 3],
+    pub domain_refinement: i32,
+}
+
+impl Default for FieldSettings {
+    fn default() -> Self {
+        Self {
+            element_order: 2,
+            domain_size: [1.0; 3],
+            domain_refinement: 2,
+        }
+    }
+}
+
+/// Bodies moving through a spacetime evolved by the 3+1 field solver of `engine_sys`. Children are
+/// the same bodies and records as those of `NBodySystem`, so they are viewed and saved the same
+/// way.
+///
+/// The native solver only evolves the spacetime for now, and doesn't advance the particles through
+/// it, so the bodies keep their positions and velocities. The `mock` feature moves them under
+/// Newtonian gravity instead.
+///
+/// The solver only exists during a solve. If it can't be created or fails, the solve leaves the
+/// bodies where they are, and the reason is kept in `error`.
+#[derive(Default, Serialize, Deserialize)]
+pub struct FieldSystem {
+    pub settings: FieldSettings,
+    #[serde(skip)]
+    solver: Option<ParticleSolver>,
+    /// Bodies in the order of the particles of the solver
+    #[serde(skip)]
+    bodies: Vec<Entity>,
+    #[serde(skip)]
+    error: Option<FieldError>,
+}
+
+impl FieldSystem {
+    pub fn new(settings: FieldSettings) -> Self {
+        Self {
+            settings,
+            ..Self::default()
+        }
+    }
+
+    /// Why the last solve could not advance the bodies, if it couldn't.
+    pub fn error(&self) -> Option<&FieldError> {
+        self.error.as_ref()
+    }
+
+    /// Collects the simulated bodies as particles, remembering which entity each one came from.
+    fn particles(&mut self, children: &mut World) -> Vec<Particle> {
+        self.bodies.clear();
+
+        children
+            .query_mut::<&NBody>()
+            .without::<Absorbed>()
+            .without::<Despawned>()
+            .into_iter()
+            .map(|(entity, body)| {
+                self.bodies.push(entity);
+                Particle {
+                    x: body.pos.x,
+                    y: body.pos.y,
+                    z: body.pos.z,
+                    velx: body.vel.x,
+                    vely: body.vel.y,
+                    velz: body.vel.z,
+                    mass: body.mass,
+                }
+            })
+            .collect()
+    }
+
+    fn update(&mut self, children: &mut World, time: f64, delta: f64) -> Result<(), FieldError> {
+        let particles = self.particles(children);
+
+        let solver = match self.solver.as_mut() {
+            Some(solver) => solver,
+            None => return Ok(()),
+        };
+
+        solver.set_particles(&particles)?;
+        solver.update(time, delta)?;
+
+        for (entity, particle) in self.bodies.iter().zip(solver.particles()?) {
+            if let Ok(body) = children.query_one_mut::<&mut NBody>(*entity) {
+                body.pos = DVec3::new(particle.x, particle.y, particle.z);
+                body.vel = DVec3::new(particle.velx, particle.vely, particle.velz);
+                body.mass = particle.mass;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drops the solver, so nothing more is solved until the next solve begins.
+    fn fail(&mut self, error: FieldError) {
+        self.solver = None;
+        self.error = Some(error);
+    }
+}
+
+impl System for FieldSystem {
+    fn solve_begin(&mut self, children: &mut World, config: &SystemConfig, time: f64) {
+        NBodySystem.solve_begin(children, config, time);
+
+        let constants = PhysicalConstants::of(config);
+
+        let settings = ParticleSolverSettings {
+            constants: Constants {
+                gravitational: constants.gravitational,
+                speed_of_light: constants.speed_of_light,
+            },
+            element_order: self.settings.element_order,
+            domain_size: self.settings.domain_size,
+            domain_refinement: self.settings.domain_refinement,
+        };
+
+        let particles = self.particles(children);
+        self.error = None;
+
+        match create_solver(&settings, &particles) {
+            Ok(solver) => self.solver = Some(solver),
+            Err(error) => self.fail(error),
+        }
+    }
+
+    fn solve_update(
+        &mut self,
+        children: &mut World,
+        _config: &SystemConfig,
+        time: f64,
+        delta: f64,
+    ) {
+        if self.solver.is_none() {
+            return;
+        }
+
+        nbody::save_records(children, time);
+
+        if let Err(error) = self.update(children, time, delta) {
+            self.fail(error);
+        }
+    }
+
+    fn solve_end(&mut self, children: &mut World, _config: &SystemConfig, time: f64) {
+        if self.solver.take().is_some() {
+            nbody::save_records(children, time);
+        }
+    }
+
+    fn view_begin(&mut self, children: &mut World, config: &SystemConfig, time: f64) {
+        NBodySystem.view_begin(children, config, time);
+    }
+
+    fn view_set_time(&mut self, children: &mut World, config: &SystemConfig, time: f64) {
+        NBodySystem.view_set_time(children, config, time);
+    }
+
+    fn view_end(&mut self, children: &mut World, config: &SystemConfig, time: f64) {
+        NBodySystem.view_end(children, config, time);
+    }
+
+    fn invalidate(&mut self, children: &mut World, config: &SystemConfig) {
+        NBodySystem.invalidate(children, config);
+    }
+
+    fn reset(&mut self, children: &mut World, config: &SystemConfig) {
+        NBodySystem.reset(children, config);
+    }
+
+    fn inspect(&self, children: &World) -> Vec<Inspected> {
+        NBodySystem.inspect(children)
+    }
+
+    fn serialize_system<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.serialize(serializer)
+    }
+
+    fn deserialize_system<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Self::deserialize(deserializer)
+    }
+
+    fn serialize_children<S>(children: &World, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        NBodySystem::serialize_children(children, serializer)
+    }
+
+    fn deserialize_children<'de, D>(deserializer: D) -> Result<World, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        NBodySystem::deserialize_children(deserializer)
+    }
+}
+
+impl RegisteredSystem for FieldSystem {
+    const KEY: &'static str = "field";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::{Subsystem, SystemNode, SystemTree};
+    use crate::gravity::GravitationalSystem;
+
+    #[test]
+    #[cfg(not(any(feature = "native", feature = "mock")))]
+    fn solves_without_backend() {
+        use crate::base::ContinuousRecord;
+        use crate::gravity::nbody::Position;
+
+        let mut tree = SystemTree::new(GravitationalSystem);
+
+        let mut field = SystemNode::new(FieldSystem::new(FieldSettings {
+            domain_refinement: 3,
+            ..FieldSettings::default()
+        }));
+        field.children_mut().spawn((
+            NBody {
+                index: 0,
+                pos: DVec3::new(0.5, 0.0, 0.0),
+                vel: DVec3::new(0.0, 0.1, 0.0),
+                mass: 1.0,
+            },
+            ContinuousRecord::<Position>::new(),
+        ));
+        tree.root_mut()
+            .children_mut()
+            .spawn((Subsystem::new(field),));
+
+        tree.solve(0.0, 1.0, 10);
+
+        let mut subsystems = tree.root().children().query::<&Subsystem>();
+        let (_entity, subsystem) = subsystems.iter().next().unwrap();
+        let field = subsystem.downcast_ref::<FieldSystem>().unwrap();
+
+        assert!(matches!(
+            field.get().error(),
+            Some(FieldError::BackendUnavailable)
+        ));
+
+        let mut bodies = field
+            .children()
+            .query::<(&NBody, &ContinuousRecord<Position>)>();
+        let (_entity, (body, record)) = bodies.iter().next().unwrap();
+        assert_eq!(body.pos, DVec3::new(0.5, 0.0, 0.0));
+        assert!(record.span().is_none());
+
+        // Loaded through the registry like any other subsystem
+        let bytes = bincode::serialize(subsystem).unwrap();
+        let loaded = bincode::deserialize::<Subsystem>(&bytes).unwrap();
+        let loaded = loaded.downcast_ref::<FieldSystem>().unwrap();
+
+        assert_eq!(loaded.get().settings.domain_refinement, 3);
+        assert_eq!(loaded.children().len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn bodies_round_trip_through_solver() {
+        let mut field = SystemNode::new(FieldSystem::default());
+        let heavy = field.children_mut().spawn((NBody {
+            index: 0,
+            pos: DVec3::new(-0.1, 0.0, 0.0),
+            vel: DVec3::ZERO,
+            mass: 4.0,
+        },));
+        let light = field.children_mut().spawn((NBody {
+            index: 1,
+            pos: DVec3::new(0.4, 0.0, 0.0),
+            vel: DVec3::new(0.0, 0.2, 0.0),
+            mass: 1.0,
+        },));
+
+        let mut tree = SystemTree::new(GravitationalSystem);
+        let subsystem = tree
+            .root_mut()
+            .children_mut()
+            .spawn((Subsystem::new(field),));
+
+        tree.solve(0.0, 0.1, 10);
+
+        let subsystem = tree.root().children().get::<Subsystem>(subsystem).unwrap();
+        let field = subsystem.downcast_ref::<FieldSystem>().unwrap();
+        assert!(field.get().error().is_none());
+
+        // Each particle comes back to the body it was made from, which keeps the centre of mass
+        // and momentum of the pair where they were
+        let heavy = field.children().get::<NBody>(heavy).unwrap();
+        let light = field.children().get::<NBody>(light).unwrap();
+        assert_eq!((heavy.index, heavy.mass), (0, 4.0));
+        assert_eq!((light.index, light.mass), (1, 1.0));
+
+        let centre = heavy.pos * heavy.mass + light.pos * light.mass;
+        let momentum = heavy.vel * heavy.mass + light.vel * light.mass;
+        assert!((centre - DVec3::new(0.0, 0.02, 0.0)).length() < 1e-9);
+        assert!((momentum - DVec3::new(0.0, 0.2, 0.0)).length() < 1e-9);
+    }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn bodies_advance_with_mock() {
+        use crate::base::ContinuousRecord;
+        use crate::gravity::nbody::Position;
+
+        // A light body falling from rest towards a heavy one
+        let mut field = SystemNode::new(FieldSystem::default());
+        field.children_mut().spawn((
+            NBody {
+                index: 0,
+                pos: DVec3::ZERO,
+                vel: DVec3::ZERO,
+                mass: 1.0,
+            },
+            ContinuousRecord::<Position>::new(),
+        ));
+        let light = field.children_mut().spawn((
+            NBody {
+                index: 1,
+                pos: DVec3::new(0.5, 0.0, 0.0),
+                vel: DVec3::ZERO,
+                mass: 1.0e-6,
+            },
+            ContinuousRecord::<Position>::new(),
+        ));
+
+        let mut tree = SystemTree::new(GravitationalSystem);
+        let subsystem = tree
+            .root_mut()
+            .children_mut()
+            .spawn((Subsystem::new(field),));
+
+        tree.solve(0.0, 0.1, 10);
+
+        let subsystem = tree.root().children().get::<Subsystem>(subsystem).unwrap();
+        let field = subsystem.downcast_ref::<FieldSystem>().unwrap();
+
+        let body = field.children().get::<NBody>(light).unwrap();
+        assert!(body.pos.x < 0.5 && body.vel.x < 0.0);
+
+        let record = field
+            .children()
+            .get::<ContinuousRecord<Position>>(light)
+            .unwrap();
+        assert_eq!(record.span(), Some((0.0, 0.1)));
+    }
+}
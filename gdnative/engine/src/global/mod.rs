@@ -3,3 +3,10 @@ mod units;
 
 pub use name::Name;
 pub use units::{Length, Mass, Time, Units};
+
+use crate::base::ConfigRegistry;
+
+/// Registers the configs of this module so they are persisted with system trees.
+pub fn register_configs(registry: &mut ConfigRegistry) {
+    registry.register::<Units>();
+}
@@ -1,7 +1,7 @@
-use crate::base::Config;
+use crate::base::{Config, PersistentConfig};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Length {
     Meter,
     Kilometer,
@@ -13,7 +13,18 @@ impl Default for Length {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl Length {
+    pub const ALL: [Self; 2] = [Self::Meter, Self::Kilometer];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Meter => "Meters (m)",
+            Self::Kilometer => "Kilometers (km)",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Time {
     Second,
     Day,
@@ -26,7 +37,19 @@ impl Default for Time {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl Time {
+    pub const ALL: [Self; 3] = [Self::Second, Self::Day, Self::Year];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Second => "Seconds (s)",
+            Self::Day => "Days (d)",
+            Self::Year => "Years (yr)",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Mass {
     Kilogram,
     SolarMass,
@@ -38,7 +61,18 @@ impl Default for Mass {
     }
 }
 
-#[derive(Debug, Default, Deserialize, Serialize)]
+impl Mass {
+    pub const ALL: [Self; 2] = [Self::Kilogram, Self::SolarMass];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Kilogram => "Kilograms (kg)",
+            Self::SolarMass => "Solar Masses (M☉)",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Units {
     length: Length,
     time: Time,
@@ -46,19 +80,34 @@ pub struct Units {
 }
 
 impl Units {
+    pub fn new(length: Length, time: Time, mass: Mass) -> Self {
+        Self { length, time, mass }
+    }
+
+    pub fn length(&self) -> Length {
+        self.length
+    }
+
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn mass(&self) -> Mass {
+        self.mass
+    }
+
     pub fn speed_of_light(&self) -> f64 {
-        let meters = 299792458.0
-            * match self.length {
-                Length::Meter => 1.0,
-                Length::Kilometer => 1000.0,
-            };
+        let meters = match self.length {
+            Length::Meter => 1.0,
+            Length::Kilometer => 1000.0,
+        };
         let seconds = match self.time {
             Time::Second => 1.0,
             Time::Day => 3600.0 * 24.0,
             Time::Year => 3600.0 * 24.0 * 365.0,
         };
 
-        meters / seconds
+        299792458.0 / meters * seconds
     }
 
     pub fn gravitational_constant(&self) -> f64 {
@@ -93,3 +142,103 @@ impl Units {
 }
 
 impl Config for Units {}
+
+impl PersistentConfig for Units {
+    const KEY: &'static str = "units";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::{ContinuousRecord, SetConfig, Subsystem, SystemNode, SystemTree};
+    use crate::gravity::nbody::{NBody, NBodySystem, Position};
+    use crate::gravity::{view_states, GravitationalSystem};
+    use glam::DVec3;
+
+    #[test]
+    fn persisted_with_tree() {
+        let units = Units {
+            length: Length::Kilometer,
+            time: Time::Year,
+            mass: Mass::SolarMass,
+        };
+
+        let mut tree = SystemTree::new(GravitationalSystem);
+        tree.config_mut().insert(units);
+
+        let bytes = bincode::serialize(&tree).unwrap();
+        let loaded: SystemTree<GravitationalSystem> = bincode::deserialize(&bytes).unwrap();
+
+        assert_eq!(loaded.config().get::<Units>(), Some(&units));
+    }
+
+    #[test]
+    fn speed_of_light() {
+        let c = Units::default().speed_of_light();
+        assert_eq!(c, 299792458.0);
+
+        let units = Units::new(Length::Kilometer, Time::Day, Mass::Kilogram);
+        assert!((units.speed_of_light() - c * 86.4).abs() / c < 1.0e-12);
+    }
+
+    #[test]
+    fn changed_by_undoable_edit() {
+        let units = Units::new(Length::Kilometer, Time::Day, Mass::SolarMass);
+
+        let mut tree = SystemTree::new(GravitationalSystem);
+        tree.edit(SetConfig::new(units)).unwrap();
+        assert_eq!(tree.config().get::<Units>(), Some(&units));
+
+        tree.undo().unwrap();
+        assert_eq!(tree.config().get::<Units>(), Some(&Units::default()));
+    }
+
+    #[test]
+    fn bodies_move_under_constants_of_units() {
+        // The earth around the sun, in kilometers, years and solar masses
+        let radius = 1.496e8;
+        let speed = 9.39e8;
+
+        let mut nbodies = SystemNode::new(NBodySystem);
+        for (index, pos, vel, mass) in [
+            (0, DVec3::ZERO, DVec3::ZERO, 1.0),
+            (1, DVec3::X * radius, DVec3::Y * speed, 3.0e-6),
+        ] {
+            nbodies.children_mut().spawn((
+                NBody {
+                    index,
+                    pos,
+                    vel,
+                    mass,
+                },
+                ContinuousRecord::<Position>::new(),
+            ));
+        }
+
+        let mut tree = SystemTree::new(GravitationalSystem);
+        tree.root_mut()
+            .children_mut()
+            .spawn((Subsystem::new(nbodies),));
+
+        let earth_after_half_a_year = |tree: &mut SystemTree<GravitationalSystem>| {
+            tree.solve(0.0, 0.5, 500);
+            tree.view(0.5);
+            view_states(tree.root())[1].1.pos
+        };
+
+        // In meters, seconds and kilograms the bodies barely attract each other
+        let earth = earth_after_half_a_year(&mut tree);
+        assert!((earth.x - radius).abs() / radius < 1.0e-6);
+
+        tree.edit(SetConfig::new(Units::new(
+            Length::Kilometer,
+            Time::Year,
+            Mass::SolarMass,
+        )))
+        .unwrap();
+        assert!(tree.is_stale());
+
+        let earth = earth_after_half_a_year(&mut tree);
+        assert!((earth.x + radius).abs() / radius < 1.0e-2);
+    }
+}
@@ -0,0 +1,330 @@
+use super::event::Despawned;
+use super::nbody::{NBody, PhysicalConstants, Position, Velocity};
+use crate::base::ContinuousRecord;
+use glam::DVec3;
+use hashbrown::HashMap;
+use hecs::{Entity, World};
+use serde::{Deserialize, Serialize};
+
+/// Marks an `NBody` as a Schwarzschild black hole. Any body which crosses its horizon is absorbed.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct BlackHole {
+    pub absorptions: Vec<Absorption>,
+}
+
+/// Records a single body crossing the horizon of a black hole.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Absorption {
+    pub time: f64,
+    pub index: usize,
+    pub mass: f64,
+    pub vel: DVec3,
+}
+
+/// Marks a body which has been swallowed by a black hole. Absorbed bodies keep their records,
+/// but no longer take part in the simulation.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Absorbed {
+    pub time: f64,
+    pub by: usize,
+}
+
+impl BlackHole {
+    pub fn new() -> Self {
+        Self {
+            absorptions: Vec::new(),
+        }
+    }
+
+    /// Reconstructs the mass of the hole at a given time from its final mass.
+    pub fn mass_at(&self, mass: f64, time: f64) -> f64 {
+        mass - self
+            .absorptions
+            .iter()
+            .filter(|absorption| absorption.time > time)
+            .map(|absorption| absorption.mass)
+            .sum::<f64>()
+    }
+
+    pub fn horizon_radius_at(&self, mass: f64, constants: &PhysicalConstants, time: f64) -> f64 {
+        schwarzschild_radius(self.mass_at(mass, time), constants)
+    }
+}
+
+pub fn schwarzschild_radius(mass: f64, constants: &PhysicalConstants) -> f64 {
+    let c = constants.speed_of_light;
+    2.0 * constants.gravitational * mass / (c * c)
+}
+
+/// Fraction of a step at which a body first comes within `radius` of a hole, moving in a straight
+/// line relative to the hole from `start` to `end`.
+fn crossing(start: DVec3, end: DVec3, radius: f64) -> Option<f64> {
+    if start.length() < radius {
+        return Some(0.0);
+    }
+
+    let path = end - start;
+    let a = path.length_squared();
+    let b = 2.0 * start.dot(path);
+    let c = start.length_squared() - radius * radius;
+    let discriminant = b * b - 4.0 * a * c;
+
+    if a == 0.0 || discriminant < 0.0 {
+        return None;
+    }
+
+    let t = (-b - discriminant.sqrt()) / (2.0 * a);
+    (0.0..=1.0).contains(&t).then_some(t)
+}
+
+/// Absorbs every body whose path over the step from `time` to `time + delta` comes within the
+/// horizon of a black hole, adding its mass and momentum to the hole. `start` holds the position
+/// and velocity of bodies at the start of the step, and bodies missing from it are taken to
+/// have been still. Heavier holes are processed first, so they may swallow lighter ones, and a
+/// hole swallows any body which crosses its horizon, even one heavier than itself.
+pub fn absorb(
+    children: &mut World,
+    constants: &PhysicalConstants,
+    time: f64,
+    delta: f64,
+    start: &HashMap<Entity, (DVec3, DVec3)>,
+) {
+    let mut holes = children
+        .query_mut::<&NBody>()
+        .with::<BlackHole>()
+        .without::<Absorbed>()
+        .without::<Despawned>()
+        .into_iter()
+        .map(|(entity, body)| (entity, body.mass))
+        .collect::<Vec<_>>();
+
+    if holes.is_empty() {
+        return;
+    }
+
+    holes.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut bodies = children
+        .query_mut::<&NBody>()
+        .without::<Absorbed>()
+        .without::<Despawned>()
+        .into_iter()
+        .map(|(entity, body)| (entity, body.clone()))
+        .collect::<Vec<_>>();
+
+    let mut absorbed = Vec::new();
+
+    for (hole_entity, _) in holes {
+        let hole_slot = match bodies.iter().position(|(e, _)| *e == hole_entity) {
+            Some(slot) => slot,
+            // Already swallowed by a heavier hole
+            None => continue,
+        };
+
+        let mut hole = bodies[hole_slot].1.clone();
+        let radius = schwarzschild_radius(hole.mass, constants);
+        let mut events = Vec::new();
+
+        let start_of = |entity: &Entity, body: &NBody| {
+            start.get(entity).copied().unwrap_or((body.pos, body.vel))
+        };
+        let (hole_start, _) = start_of(&hole_entity, &hole);
+
+        bodies.retain(|(entity, body)| {
+            if *entity == hole_entity {
+                return true;
+            }
+
+            let (pos_start, vel_start) = start_of(entity, body);
+            let t = match crossing(pos_start - hole_start, body.pos - hole.pos, radius) {
+                Some(t) => t,
+                None => return true,
+            };
+
+            // The body is absorbed where it crossed the horizon
+            let at = time + delta * t;
+            let pos = pos_start.lerp(body.pos, t);
+            let vel = vel_start.lerp(body.vel, t);
+
+            let mass = hole.mass + body.mass;
+            if mass > 0.0 {
+                hole.vel = (hole.vel * hole.mass + vel * body.mass) / mass;
+            }
+            hole.mass = mass;
+
+            events.push(Absorption {
+                time: at,
+                index: body.index,
+                mass: body.mass,
+                vel,
+            });
+            absorbed.push((*entity, at, pos, vel, hole.index));
+
+            false
+        });
+
+        if let Some((_, body)) = bodies.iter_mut().find(|(e, _)| *e == hole_entity) {
+            *body = hole.clone();
+        }
+
+        if let Ok((body, black_hole)) =
+            children.query_one_mut::<(&mut NBody, &mut BlackHole)>(hole_entity)
+        {
+            *body = hole;
+            black_hole.absorptions.extend(events);
+        }
+    }
+
+    for (entity, time, pos, vel, by) in absorbed {
+        if let Ok(record) = children.query_one_mut::<&mut ContinuousRecord<Position>>(entity) {
+            record.save(time, Position { pos });
+        }
+        if let Ok(record) = children.query_one_mut::<&mut ContinuousRecord<Velocity>>(entity) {
+            record.save(time, Velocity { vel });
+        }
+
+        let _ = children.insert_one(entity, Absorbed { time, by });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::{SetConfig, Subsystem, SystemNode, SystemTree};
+    use crate::global::{Length, Mass, Time, Units};
+    use crate::gravity::{nbody::NBodySystem, view_states, GravitationalSystem};
+
+    fn body(index: usize, pos: DVec3, vel: DVec3, mass: f64) -> NBody {
+        NBody {
+            index,
+            pos,
+            vel,
+            mass,
+        }
+    }
+
+    #[test]
+    fn absorption() {
+        let constants = PhysicalConstants::NATURAL;
+        let mut world = World::new();
+
+        let hole_mass = 1.0;
+        let radius = schwarzschild_radius(hole_mass, &constants);
+        assert_eq!(radius, 2.0);
+
+        let hole = world.spawn((
+            body(0, DVec3::ZERO, DVec3::ZERO, hole_mass),
+            BlackHole::new(),
+        ));
+        let inside = world.spawn((body(
+            1,
+            DVec3::new(radius * 0.5, 0.0, 0.0),
+            DVec3::new(0.0, 0.5, 0.0),
+            0.01,
+        ),));
+        let outside = world.spawn((body(
+            2,
+            DVec3::new(radius * 2.0, 0.0, 0.0),
+            DVec3::ZERO,
+            0.0,
+        ),));
+
+        absorb(&mut world, &constants, 0.0, 1.0, &HashMap::new());
+
+        assert!(world.get::<Absorbed>(inside).is_ok());
+        assert!(world.get::<Absorbed>(outside).is_err());
+
+        let body = world.get::<NBody>(hole).unwrap();
+        let black_hole = world.get::<BlackHole>(hole).unwrap();
+
+        let momentum = 0.01 * 0.5;
+        assert!((body.mass - (hole_mass + 0.01)).abs() < 1.0e-12);
+        assert!((body.vel.y * body.mass - momentum).abs() / momentum < 1.0e-12);
+        assert_eq!(black_hole.absorptions.len(), 1);
+        assert_eq!(black_hole.absorptions[0].index, 1);
+        assert!((black_hole.mass_at(body.mass, -1.0) - hole_mass).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn absorbs_heavier_bodies() {
+        let constants = PhysicalConstants::NATURAL;
+        let mut world = World::new();
+
+        let hole = world.spawn((body(0, DVec3::ZERO, DVec3::ZERO, 1.0), BlackHole::new()));
+        let star = world.spawn((body(1, DVec3::new(1.0, 0.0, 0.0), DVec3::ZERO, 5.0),));
+
+        absorb(&mut world, &constants, 0.0, 1.0, &HashMap::new());
+
+        assert!(world.get::<Absorbed>(star).is_ok());
+        assert_eq!(world.get::<NBody>(hole).unwrap().mass, 6.0);
+    }
+
+    #[test]
+    fn absorbs_bodies_passing_through() {
+        let constants = PhysicalConstants::NATURAL;
+        let mut world = World::new();
+
+        world.spawn((body(0, DVec3::ZERO, DVec3::ZERO, 1.0), BlackHole::new()));
+        let passing = world.spawn((body(
+            1,
+            DVec3::new(3.0, 0.0, 0.0),
+            DVec3::new(6.0, 0.0, 0.0),
+            0.0,
+        ),));
+        let missing = world.spawn((body(
+            2,
+            DVec3::new(3.0, 3.0, 0.0),
+            DVec3::new(6.0, 0.0, 0.0),
+            0.0,
+        ),));
+
+        // Both end outside the horizon, but only one went through it
+        let start = [
+            (
+                passing,
+                (DVec3::new(-3.0, 0.0, 0.0), DVec3::new(6.0, 0.0, 0.0)),
+            ),
+            (
+                missing,
+                (DVec3::new(-3.0, 3.0, 0.0), DVec3::new(6.0, 0.0, 0.0)),
+            ),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+        absorb(&mut world, &constants, 2.0, 1.0, &start);
+
+        assert!(world.get::<Absorbed>(missing).is_err());
+
+        let absorbed = world.get::<Absorbed>(passing).unwrap();
+        assert!((absorbed.time - (2.0 + 1.0 / 6.0)).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn horizons_follow_units() {
+        let mut nbodies = SystemNode::new(NBodySystem);
+        nbodies
+            .children_mut()
+            .spawn((body(0, DVec3::ZERO, DVec3::ZERO, 1.0), BlackHole::new()));
+
+        let mut tree = SystemTree::new(GravitationalSystem);
+        tree.root_mut()
+            .children_mut()
+            .spawn((Subsystem::new(nbodies),));
+
+        let horizon = |tree: &mut SystemTree<GravitationalSystem>| {
+            tree.view(0.0);
+            view_states(tree.root())[0].1.horizon_radius
+        };
+
+        // A hole of one kilogram, in meters
+        let radius = horizon(&mut tree);
+        assert!((radius - 1.485e-27).abs() / radius < 1.0e-3);
+
+        // A hole of one solar mass, in kilometers
+        let units = Units::new(Length::Kilometer, Time::Second, Mass::SolarMass);
+        tree.edit(SetConfig::new(units)).unwrap();
+        let radius = horizon(&mut tree);
+        assert!((radius - 2.954).abs() / radius < 1.0e-3);
+    }
+}
@@ -0,0 +1,551 @@
+use super::blackhole::{Absorbed, BlackHole};
+use super::event::Despawned;
+use super::nbody::{NBody, Name, Position, Star, Velocity};
+use super::{first_nbody_mut, nbody_mut, BodyId, GravitationalSystem};
+use crate::base::{Command, ContinuousRecord, EditError, SystemConfig, SystemNode};
+use glam::DVec3;
+use hecs::{Entity, EntityBuilder, World};
+
+fn find_body(children: &mut World, index: usize) -> Option<Entity> {
+    children
+        .query_mut::<&NBody>()
+        .into_iter()
+        .find(|(_e, body)| body.index == index)
+        .map(|(entity, _body)| entity)
+}
+
+/// Adds a body to the first n-body subsystem.
+pub struct AddBody {
+    body: NBody,
+    black_hole: bool,
+}
+
+impl AddBody {
+    pub fn new(body: NBody) -> Self {
+        Self {
+            body,
+            black_hole: false,
+        }
+    }
+
+    pub fn black_hole(body: NBody) -> Self {
+        Self {
+            body,
+            black_hole: true,
+        }
+    }
+}
+
+impl Command<GravitationalSystem> for AddBody {
+    fn apply(
+        &mut self,
+        root: &mut SystemNode<GravitationalSystem>,
+        _config: &mut SystemConfig,
+    ) -> Result<(), EditError> {
+        let children = first_nbody_mut(root)
+            .ok_or(EditError::SubsystemNotFound)?
+            .children_mut();
+
+        if find_body(children, self.body.index).is_some() {
+            return Err(EditError::DuplicateBody(self.body.index));
+        }
+
+        let entity = children.spawn((self.body.clone(), ContinuousRecord::<Position>::new()));
+
+        if self.black_hole {
+            let _ = children.insert_one(entity, BlackHole::new());
+        }
+
+        Ok(())
+    }
+
+    fn undo(&mut self, root: &mut SystemNode<GravitationalSystem>, _config: &mut SystemConfig) {
+        if let Some(nbody) = first_nbody_mut(root) {
+            if let Some(entity) = find_body(nbody.children_mut(), self.body.index) {
+                let _ = nbody.children_mut().despawn(entity);
+            }
+        }
+    }
+}
+
+/// Everything a body owned when it was removed.
+struct RemovedBody {
+    body: NBody,
+    positions: Option<ContinuousRecord<Position>>,
+    velocities: Option<ContinuousRecord<Velocity>>,
+    black_hole: Option<BlackHole>,
+    absorbed: Option<Absorbed>,
+    despawned: Option<Despawned>,
+    star: Option<Star>,
+    name: Option<Name>,
+}
+
+/// Removes a body, along with its records, from its n-body subsystem.
+pub struct RemoveBody {
+    id: BodyId,
+    removed: Option<RemovedBody>,
+}
+
+impl RemoveBody {
+    pub fn new(id: BodyId) -> Self {
+        Self { id, removed: None }
+    }
+}
+
+impl Command<GravitationalSystem> for RemoveBody {
+    fn apply(
+        &mut self,
+        root: &mut SystemNode<GravitationalSystem>,
+        _config: &mut SystemConfig,
+    ) -> Result<(), EditError> {
+        let index = self.id.index;
+        let children = nbody_mut(root, self.id.subsystem)
+            .ok_or(EditError::SubsystemNotFound)?
+            .children_mut();
+
+        let entity = find_body(children, index).ok_or(EditError::BodyNotFound(index))?;
+
+        let body = children
+            .remove_one::<NBody>(entity)
+            .map_err(|_| EditError::BodyNotFound(index))?;
+
+        self.removed = Some(RemovedBody {
+            body,
+            positions: children.remove_one(entity).ok(),
+            velocities: children.remove_one(entity).ok(),
+            black_hole: children.remove_one(entity).ok(),
+            absorbed: children.remove_one(entity).ok(),
+            despawned: children.remove_one(entity).ok(),
+            star: children.remove_one(entity).ok(),
+            name: children.remove_one(entity).ok(),
+        });
+
+        let _ = children.despawn(entity);
+
+        Ok(())
+    }
+
+    fn undo(&mut self, root: &mut SystemNode<GravitationalSystem>, _config: &mut SystemConfig) {
+        let (nbody, removed) = match (nbody_mut(root, self.id.subsystem), self.removed.take()) {
+            (Some(nbody), Some(removed)) => (nbody, removed),
+            _ => return,
+        };
+
+        let mut builder = EntityBuilder::new();
+        builder.add(removed.body);
+
+        if let Some(positions) = removed.positions {
+            builder.add(positions);
+        }
+        if let Some(velocities) = removed.velocities {
+            builder.add(velocities);
+        }
+        if let Some(black_hole) = removed.black_hole {
+            builder.add(black_hole);
+        }
+        if let Some(absorbed) = removed.absorbed {
+            builder.add(absorbed);
+        }
+        if let Some(despawned) = removed.despawned {
+            builder.add(despawned);
+        }
+        if let Some(star) = removed.star {
+            builder.add(star);
+        }
+        if let Some(name) = removed.name {
+            builder.add(name);
+        }
+
+        nbody.children_mut().spawn(builder.build());
+    }
+}
+
+/// A property of a body which can be edited.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BodyProperty {
+    Position(DVec3),
+    Velocity(DVec3),
+    Mass(f64),
+    Name(String),
+    Temperature(f64),
+}
+
+impl BodyProperty {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Position(_) => "position",
+            Self::Velocity(_) => "velocity",
+            Self::Mass(_) => "mass",
+            Self::Name(_) => "name",
+            Self::Temperature(_) => "temperature",
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        match self {
+            Self::Position(v) | Self::Velocity(v) => v.is_finite(),
+            Self::Mass(x) | Self::Temperature(x) => x.is_finite() && *x >= 0.0,
+            Self::Name(name) => !name.trim().is_empty(),
+        }
+    }
+}
+
+/// Changes a single property of a body. Names and temperatures are added to bodies which do not
+/// have them yet, and removed again on undo.
+pub struct SetBody {
+    id: BodyId,
+    property: BodyProperty,
+    /// The value replaced by the last apply, or `None` if the body had no such property
+    previous: Option<Option<BodyProperty>>,
+}
+
+impl SetBody {
+    pub fn new(id: BodyId, property: BodyProperty) -> Self {
+        Self {
+            id,
+            property,
+            previous: None,
+        }
+    }
+}
+
+/// Sets a property of a body, returning the value it replaced, or `None` if the body had no such
+/// property.
+fn set_property(
+    children: &mut World,
+    entity: Entity,
+    index: usize,
+    property: BodyProperty,
+) -> Result<Option<BodyProperty>, EditError> {
+    let not_found = |_| EditError::BodyNotFound(index);
+
+    let previous = match property {
+        BodyProperty::Name(name) => {
+            let previous = children.remove_one::<Name>(entity).ok();
+            let _ = children.insert_one(entity, Name { name });
+            previous.map(|previous| BodyProperty::Name(previous.name))
+        }
+        BodyProperty::Temperature(temperature) => {
+            let previous = children.remove_one::<Star>(entity).ok();
+            let _ = children.insert_one(entity, Star { temperature });
+            previous.map(|previous| BodyProperty::Temperature(previous.temperature))
+        }
+        BodyProperty::Position(pos) => {
+            let mut body = children.get_mut::<NBody>(entity).map_err(not_found)?;
+            let previous = std::mem::replace(&mut body.pos, pos);
+            Some(BodyProperty::Position(previous))
+        }
+        BodyProperty::Velocity(vel) => {
+            let mut body = children.get_mut::<NBody>(entity).map_err(not_found)?;
+            let previous = std::mem::replace(&mut body.vel, vel);
+            Some(BodyProperty::Velocity(previous))
+        }
+        BodyProperty::Mass(mass) => {
+            let mut body = children.get_mut::<NBody>(entity).map_err(not_found)?;
+            let previous = std::mem::replace(&mut body.mass, mass);
+            Some(BodyProperty::Mass(previous))
+        }
+    };
+
+    Ok(previous)
+}
+
+impl Command<GravitationalSystem> for SetBody {
+    fn apply(
+        &mut self,
+        root: &mut SystemNode<GravitationalSystem>,
+        _config: &mut SystemConfig,
+    ) -> Result<(), EditError> {
+        let index = self.id.index;
+
+        if !self.property.is_valid() {
+            return Err(EditError::InvalidValue {
+                index,
+                property: self.property.name(),
+            });
+        }
+
+        let children = nbody_mut(root, self.id.subsystem)
+            .ok_or(EditError::SubsystemNotFound)?
+            .children_mut();
+
+        let entity = find_body(children, index).ok_or(EditError::BodyNotFound(index))?;
+
+        self.previous = Some(set_property(
+            children,
+            entity,
+            index,
+            self.property.clone(),
+        )?);
+
+        Ok(())
+    }
+
+    fn undo(&mut self, root: &mut SystemNode<GravitationalSystem>, _config: &mut SystemConfig) {
+        let previous = match self.previous.take() {
+            Some(previous) => previous,
+            None => return,
+        };
+
+        let children = match nbody_mut(root, self.id.subsystem) {
+            Some(nbody) => nbody.children_mut(),
+            None => return,
+        };
+
+        if let Some(entity) = find_body(children, self.id.index) {
+            match (previous, &self.property) {
+                (Some(previous), _) => {
+                    let _ = set_property(children, entity, self.id.index, previous);
+                }
+                (None, BodyProperty::Name(_)) => {
+                    let _ = children.remove_one::<Name>(entity);
+                }
+                (None, BodyProperty::Temperature(_)) => {
+                    let _ = children.remove_one::<Star>(entity);
+                }
+                (None, _) => {}
+            }
+        }
+    }
+
+    /// Names and temperatures only change how a body is shown.
+    fn invalidates(&self) -> bool {
+        !matches!(
+            self.property,
+            BodyProperty::Name(_) | BodyProperty::Temperature(_)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::{Subsystem, SystemTree};
+    use crate::gravity::nbody::NBodySystem;
+
+    fn body(index: usize, x: f64) -> NBody {
+        NBody {
+            index,
+            pos: DVec3::new(x, 0.0, 0.0),
+            vel: DVec3::ZERO,
+            mass: 1.0,
+        }
+    }
+
+    /// Id of a body in the first subsystem of the tree.
+    fn id(tree: &SystemTree<GravitationalSystem>, index: usize) -> BodyId {
+        let subsystem = tree
+            .root()
+            .children()
+            .query::<&Subsystem>()
+            .iter()
+            .map(|(entity, _subsystem)| entity)
+            .min()
+            .unwrap();
+
+        BodyId { subsystem, index }
+    }
+
+    fn bodies(tree: &SystemTree<GravitationalSystem>) -> Vec<(usize, DVec3)> {
+        let mut bodies = Vec::new();
+
+        for (_e, subsystem) in tree.root().children().query::<&Subsystem>().iter() {
+            let nbody = subsystem.downcast_ref::<NBodySystem>().unwrap();
+            for (_e, body) in nbody.children().query::<&NBody>().iter() {
+                bodies.push((body.index, body.pos));
+            }
+        }
+
+        bodies.sort_by_key(|body| body.0);
+        bodies
+    }
+
+    #[test]
+    fn undo_redo_transactions() {
+        let mut tree = SystemTree::new(GravitationalSystem);
+
+        assert!(tree.edit(AddBody::new(body(0, 1.0))).is_err());
+
+        tree.edit(crate::base::AddSubsystem::new(Subsystem::new(
+            SystemNode::new(NBodySystem),
+        )))
+        .unwrap();
+        tree.edit(AddBody::new(body(0, 1.0))).unwrap();
+        tree.edit(AddBody::new(body(1, -1.0))).unwrap();
+        assert!(tree.edit(AddBody::new(body(1, 2.0))).is_err());
+
+        let initial = bodies(&tree);
+
+        tree.solve(0.0, 1.0, 9);
+        assert!(!tree.is_stale());
+
+        tree.begin_transaction().unwrap();
+        tree.edit(RemoveBody::new(id(&tree, 1))).unwrap();
+        tree.edit(SetBody::new(
+            id(&tree, 0),
+            BodyProperty::Position(DVec3::new(0.0, 5.0, 0.0)),
+        ))
+        .unwrap();
+        assert!(tree.undo().is_err());
+        tree.commit_transaction().unwrap();
+
+        assert!(tree.is_stale());
+        assert_eq!(bodies(&tree), vec![(0, DVec3::new(0.0, 5.0, 0.0))]);
+
+        tree.undo().unwrap();
+        assert!(!tree.is_stale());
+        assert_eq!(bodies(&tree), initial);
+
+        tree.redo().unwrap();
+        assert!(tree.is_stale());
+        assert_eq!(bodies(&tree), vec![(0, DVec3::new(0.0, 5.0, 0.0))]);
+
+        tree.begin_transaction().unwrap();
+        tree.edit(RemoveBody::new(id(&tree, 0))).unwrap();
+        tree.rollback_transaction().unwrap();
+        assert_eq!(bodies(&tree), vec![(0, DVec3::new(0.0, 5.0, 0.0))]);
+        assert!(tree.can_undo());
+        assert!(!tree.can_redo());
+    }
+
+    #[test]
+    fn validated_and_cosmetic_edits() {
+        let mut tree = SystemTree::new(GravitationalSystem);
+        tree.edit(crate::base::AddSubsystem::new(Subsystem::new(
+            SystemNode::new(NBodySystem),
+        )))
+        .unwrap();
+        tree.edit(AddBody::new(body(0, 1.0))).unwrap();
+        tree.solve(0.0, 1.0, 9);
+
+        assert!(tree
+            .edit(SetBody::new(id(&tree, 0), BodyProperty::Mass(-1.0)))
+            .is_err());
+        assert!(tree
+            .edit(SetBody::new(
+                id(&tree, 0),
+                BodyProperty::Name(String::from(" "))
+            ))
+            .is_err());
+
+        tree.edit(SetBody::new(
+            id(&tree, 0),
+            BodyProperty::Name(String::from("Sun")),
+        ))
+        .unwrap();
+        tree.edit(SetBody::new(
+            id(&tree, 0),
+            BodyProperty::Temperature(5772.0),
+        ))
+        .unwrap();
+        assert!(!tree.is_stale());
+
+        tree.edit(SetBody::new(id(&tree, 0), BodyProperty::Mass(2.0)))
+            .unwrap();
+        assert!(tree.is_stale());
+
+        tree.undo().unwrap();
+        tree.undo().unwrap();
+        tree.undo().unwrap();
+        assert!(!tree.is_stale());
+
+        let names = tree
+            .root()
+            .children()
+            .query::<&Subsystem>()
+            .iter()
+            .next()
+            .map(|(_e, subsystem)| {
+                let children = subsystem.downcast_ref::<NBodySystem>().unwrap().children();
+                children.query::<&Name>().iter().count()
+            });
+        assert_eq!(names, Some(0));
+    }
+
+    #[test]
+    fn edits_after_solve_change_initial_state() {
+        let mut tree = SystemTree::new(GravitationalSystem);
+        tree.edit(crate::base::AddSubsystem::new(Subsystem::new(
+            SystemNode::new(NBodySystem),
+        )))
+        .unwrap();
+        tree.edit(AddBody::new(body(0, 1.0))).unwrap();
+        tree.edit(AddBody::new(body(1, -1.0))).unwrap();
+        tree.solve(0.0, 1.0, 9);
+
+        let start = DVec3::new(0.0, 4.0, 0.0);
+        tree.edit(SetBody::new(id(&tree, 0), BodyProperty::Position(start)))
+            .unwrap();
+
+        // The other body is back where it started, rather than where the solve left it
+        assert_eq!(
+            bodies(&tree),
+            vec![(0, start), (1, DVec3::new(-1.0, 0.0, 0.0))]
+        );
+        assert!(tree.extend(2.0, 9).is_err());
+
+        tree.solve(0.0, 1.0, 9);
+
+        for (_e, subsystem) in tree.root().children().query::<&Subsystem>().iter() {
+            let nbody = subsystem.downcast_ref::<NBodySystem>().unwrap();
+            for (_e, (body, record)) in nbody
+                .children()
+                .query::<(&NBody, &ContinuousRecord<Position>)>()
+                .iter()
+            {
+                let expected = match body.index {
+                    0 => start,
+                    _ => DVec3::new(-1.0, 0.0, 0.0),
+                };
+                assert_eq!(record.load(0.0).pos, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn edits_address_bodies_by_subsystem() {
+        let mut tree = SystemTree::new(GravitationalSystem);
+        for x in [1.0, -1.0] {
+            let mut nbodies = SystemNode::new(NBodySystem);
+            nbodies.children_mut().spawn((body(0, x),));
+            tree.edit(crate::base::AddSubsystem::new(Subsystem::new(nbodies)))
+                .unwrap();
+        }
+
+        let first = id(&tree, 0);
+        let second = tree
+            .root()
+            .children()
+            .query::<&Subsystem>()
+            .iter()
+            .map(|(entity, _subsystem)| BodyId {
+                subsystem: entity,
+                index: 0,
+            })
+            .max()
+            .unwrap();
+
+        let position = |tree: &SystemTree<GravitationalSystem>, id: BodyId| {
+            let subsystem = tree.root().children().get::<Subsystem>(id.subsystem).ok()?;
+            let nbody = subsystem.downcast_ref::<NBodySystem>()?;
+            let mut bodies = nbody.children().query::<&NBody>();
+            let pos = bodies
+                .iter()
+                .find(|(_e, body)| body.index == id.index)
+                .map(|(_e, body)| body.pos);
+            pos
+        };
+
+        tree.edit(SetBody::new(second, BodyProperty::Position(DVec3::Y)))
+            .unwrap();
+        assert_eq!(position(&tree, first), Some(DVec3::X));
+        assert_eq!(position(&tree, second), Some(DVec3::Y));
+
+        tree.edit(RemoveBody::new(second)).unwrap();
+        assert_eq!(position(&tree, first), Some(DVec3::X));
+        assert_eq!(position(&tree, second), None);
+
+        tree.undo().unwrap();
+        assert_eq!(position(&tree, second), Some(DVec3::Y));
+    }
+}
@@ -0,0 +1,77 @@
+use super::blackhole::Absorbed;
+use super::nbody::{NBody, Position, Velocity};
+use crate::base::ContinuousRecord;
+use glam::DVec3;
+use hecs::World;
+use serde::{Deserialize, Serialize};
+
+/// Events which can be scheduled on a gravitational system.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum GravitationalEvent {
+    /// Instantly changes the velocity of a body
+    Impulse { index: usize, dv: DVec3 },
+    /// Adds a new body, which is simulated from the time of the event onwards
+    Spawn(NBody),
+    /// Removes a body from the simulation. Its records are kept up to the time of the event.
+    Despawn { index: usize },
+}
+
+/// Marks a body which was added by a `Spawn` event, so it is removed again when the tree is reset.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Spawned;
+
+/// Marks a body which has been removed by a `Despawn` event.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Despawned {
+    pub time: f64,
+}
+
+/// Applies an event to the children of an n-body system. Events which refer to bodies that do not
+/// exist, or are no longer simulated, are ignored.
+pub fn apply(children: &mut World, time: f64, event: &GravitationalEvent) {
+    match event {
+        GravitationalEvent::Impulse { index, dv } => {
+            if let Some((_e, body)) = children
+                .query_mut::<&mut NBody>()
+                .without::<Absorbed>()
+                .without::<Despawned>()
+                .into_iter()
+                .find(|(_e, body)| body.index == *index)
+            {
+                body.vel += *dv;
+            }
+        }
+        GravitationalEvent::Spawn(body) => {
+            let mut positions = ContinuousRecord::new();
+            positions.save(time, Position { pos: body.pos });
+
+            let mut velocities = ContinuousRecord::new();
+            velocities.save(time, Velocity { vel: body.vel });
+
+            children.spawn((body.clone(), positions, velocities, Spawned));
+        }
+        GravitationalEvent::Despawn { index } => {
+            let entity = children
+                .query_mut::<(
+                    &NBody,
+                    &mut ContinuousRecord<Position>,
+                    Option<&mut ContinuousRecord<Velocity>>,
+                )>()
+                .without::<Absorbed>()
+                .without::<Despawned>()
+                .into_iter()
+                .find(|(_e, (body, _positions, _velocities))| body.index == *index)
+                .map(|(entity, (body, positions, velocities))| {
+                    positions.save(time, Position { pos: body.pos });
+                    if let Some(velocities) = velocities {
+                        velocities.save(time, Velocity { vel: body.vel });
+                    }
+                    entity
+                });
+
+            if let Some(entity) = entity {
+                let _ = children.insert_one(entity, Despawned { time });
+            }
+        }
+    }
+}
@@ -0,0 +1,603 @@
+use crate::base::SpatialIndex;
+use glam::DVec3;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::ops::{Add, Mul, Sub};
+use std::sync::OnceLock;
+
+/// Scale of the gaussian splitting gravity between the mesh and direct summation, in cells
+const SPLIT: f64 = 1.25;
+/// Distance beyond which the short range force is neglected, in units of `SPLIT`
+const CUTOFF: f64 = 6.0;
+/// Smallest mesh, which keeps the short range cutoff within half of a periodic box
+const MIN_GRID: usize = 16;
+
+/// How the mass of a body is spread over the mesh, and how forces are read back from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Assignment {
+    /// Cloud in cell, spreading a body over the 8 nearest mesh points
+    Cic,
+    /// Triangular shaped cloud, spreading a body over the 27 nearest mesh points
+    Tsc,
+}
+
+impl Assignment {
+    /// Mesh points along one axis which a body touches.
+    fn width(self) -> usize {
+        match self {
+            Self::Cic => 2,
+            Self::Tsc => 3,
+        }
+    }
+
+    /// First mesh point touched by a body at `u` cells along one axis, and the weight of each
+    /// point from there on.
+    fn weights(self, u: f64) -> (i64, [f64; 3]) {
+        match self {
+            Self::Cic => {
+                let i = u.floor();
+                let f = u - i;
+                (i as i64, [1.0 - f, f, 0.0])
+            }
+            Self::Tsc => {
+                let i = u.round();
+                let d = u - i;
+                (
+                    i as i64 - 1,
+                    [
+                        0.5 * (0.5 - d) * (0.5 - d),
+                        0.75 - d * d,
+                        0.5 * (0.5 + d) * (0.5 + d),
+                    ],
+                )
+            }
+        }
+    }
+
+    /// Fourier transform of the assignment along one axis, at `x` radians per cell.
+    fn window(self, x: f64) -> f64 {
+        let sinc = if x == 0.0 { 1.0 } else { x.sin() / x };
+        sinc.powi(self.width() as i32)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Boundary {
+    /// Bodies are alone in space. The mesh is fitted around them on every step.
+    Isolated,
+    /// Space repeats every `size` along each axis, from a cube centered on the origin.
+    Periodic { size: f64 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MeshSettings {
+    /// Mesh points along each axis, rounded up to a power of two of at least 16
+    pub grid: usize,
+    pub assignment: Assignment,
+    pub boundary: Boundary,
+    /// Whether bodies within a few cells of each other also attract directly (P³M). Without it,
+    /// gravity is smoothed over a little more than a cell.
+    pub short_range: bool,
+}
+
+impl Default for MeshSettings {
+    fn default() -> Self {
+        Self {
+            grid: 32,
+            assignment: Assignment::Tsc,
+            boundary: Boundary::Isolated,
+            short_range: true,
+        }
+    }
+}
+
+/// Particle-mesh gravity. Masses are deposited on a mesh, the potential is found by convolving
+/// them with the Green's function of Poisson's equation using FFTs, and forces are interpolated
+/// back to the bodies. The cost grows with the size of the mesh rather than the square of the
+/// number of bodies.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ParticleMesh {
+    settings: MeshSettings,
+    /// Green's function in Fourier space, in units of cells
+    #[serde(skip)]
+    kernel: OnceLock<Vec<f64>>,
+}
+
+impl ParticleMesh {
+    pub fn new(settings: MeshSettings) -> Self {
+        Self {
+            settings,
+            kernel: OnceLock::new(),
+        }
+    }
+
+    pub fn settings(&self) -> &MeshSettings {
+        &self.settings
+    }
+
+    /// Mesh points along each axis of the bodies' region.
+    fn grid(&self) -> usize {
+        self.settings.grid.max(MIN_GRID).next_power_of_two()
+    }
+
+    /// Mesh points along each axis of the FFTs, which are doubled for isolated bodies so the
+    /// periodic convolution does not wrap around.
+    fn fft_grid(&self) -> usize {
+        match self.settings.boundary {
+            Boundary::Isolated => self.grid() * 2,
+            Boundary::Periodic { .. } => self.grid(),
+        }
+    }
+
+    /// Acceleration of every body due to the gravity of the others.
+    pub fn accelerations(&self, positions: &[DVec3], masses: &[f64], g: f64) -> Vec<DVec3> {
+        let grid = self.grid();
+        let n = self.fft_grid();
+
+        let (origin, cell) = match self.settings.boundary {
+            Boundary::Isolated => {
+                let (min, max) = positions.iter().fold(
+                    (DVec3::splat(f64::INFINITY), DVec3::splat(f64::NEG_INFINITY)),
+                    |(min, max), pos| (min.min(*pos), max.max(*pos)),
+                );
+                let extent = (max - min).max_element();
+                if extent <= 0.0 {
+                    return vec![DVec3::ZERO; positions.len()];
+                }
+
+                // Two cells of margin keep the stencils of deposit and gradient on the mesh
+                let cell = extent / (grid - 5) as f64;
+                (min - DVec3::splat(2.0 * cell), cell)
+            }
+            Boundary::Periodic { size } => (DVec3::splat(-0.5 * size), size / grid as f64),
+        };
+
+        let index = |x: i64, y: i64, z: i64| {
+            let wrap = |i: i64| i.rem_euclid(n as i64) as usize;
+            (wrap(x) * n + wrap(y)) * n + wrap(z)
+        };
+
+        let assignment = self.settings.assignment;
+        let width = assignment.width() as i64;
+        let stencil = |pos: DVec3| {
+            let u = (pos - origin) / cell;
+            [
+                assignment.weights(u.x),
+                assignment.weights(u.y),
+                assignment.weights(u.z),
+            ]
+        };
+
+        let mut mesh = vec![Complex::ZERO; n * n * n];
+        for (pos, mass) in positions.iter().zip(masses) {
+            let [(x0, wx), (y0, wy), (z0, wz)] = stencil(*pos);
+            for dx in 0..width {
+                for dy in 0..width {
+                    for dz in 0..width {
+                        let weight = wx[dx as usize] * wy[dy as usize] * wz[dz as usize];
+                        mesh[index(x0 + dx, y0 + dy, z0 + dz)].re += mass * weight;
+                    }
+                }
+            }
+        }
+
+        let kernel = self.kernel.get_or_init(|| self.kernel());
+        fft3(&mut mesh, n, false);
+        for (value, k) in mesh.iter_mut().zip(kernel) {
+            *value = *value * *k;
+        }
+        fft3(&mut mesh, n, true);
+
+        // Fourth order central difference of the potential, in units of cells
+        let potential = |x, y, z| mesh[index(x, y, z)].re;
+        let gradient = |x: i64, y: i64, z: i64| {
+            let axis = |a: fn(i64) -> (i64, i64, i64)| {
+                let at = |d: i64| {
+                    let (dx, dy, dz) = a(d);
+                    potential(x + dx, y + dy, z + dz)
+                };
+                (8.0 * (at(1) - at(-1)) - (at(2) - at(-2))) / 12.0
+            };
+            DVec3::new(
+                axis(|d| (d, 0, 0)),
+                axis(|d| (0, d, 0)),
+                axis(|d| (0, 0, d)),
+            )
+        };
+
+        let mut accelerations = positions
+            .iter()
+            .map(|pos| {
+                let [(x0, wx), (y0, wy), (z0, wz)] = stencil(*pos);
+                let mut grad = DVec3::ZERO;
+                for dx in 0..width {
+                    for dy in 0..width {
+                        for dz in 0..width {
+                            let weight = wx[dx as usize] * wy[dy as usize] * wz[dz as usize];
+                            grad += gradient(x0 + dx, y0 + dy, z0 + dz) * weight;
+                        }
+                    }
+                }
+                -grad * (g / (cell * cell))
+            })
+            .collect::<Vec<_>>();
+
+        if self.settings.short_range {
+            self.add_short_range(&mut accelerations, positions, masses, g, cell);
+        }
+
+        accelerations
+    }
+
+    /// Adds the part of gravity the mesh smooths out, between bodies closer than the cutoff.
+    fn add_short_range(
+        &self,
+        accelerations: &mut [DVec3],
+        positions: &[DVec3],
+        masses: &[f64],
+        g: f64,
+        cell: f64,
+    ) {
+        let split = SPLIT * cell;
+        let cutoff = CUTOFF * split;
+
+        // Periodic bodies are wrapped into the box, and also look for neighbours through the
+        // faces of the box they are near.
+        let (positions, images) = match self.settings.boundary {
+            Boundary::Isolated => (positions.to_vec(), vec![DVec3::ZERO]),
+            Boundary::Periodic { size } => {
+                let wrap = |x: f64| (x + 0.5 * size).rem_euclid(size) - 0.5 * size;
+                let wrapped = positions
+                    .iter()
+                    .map(|pos| DVec3::new(wrap(pos.x), wrap(pos.y), wrap(pos.z)))
+                    .collect();
+
+                let mut images = Vec::new();
+                for x in -1..=1 {
+                    for y in -1..=1 {
+                        for z in -1..=1 {
+                            images.push(DVec3::new(x as f64, y as f64, z as f64) * size);
+                        }
+                    }
+                }
+                (wrapped, images)
+            }
+        };
+
+        let half = match self.settings.boundary {
+            Boundary::Isolated => f64::INFINITY,
+            Boundary::Periodic { size } => 0.5 * size,
+        };
+
+        let index = SpatialIndex::new(positions.iter().copied().enumerate().collect());
+
+        for (i, pos) in positions.iter().enumerate() {
+            for image in images.iter() {
+                let query = *pos + *image;
+
+                // Images whose neighbourhood lies entirely outside the box find nothing
+                if (query.abs() - DVec3::splat(cutoff)).max_element() > half {
+                    continue;
+                }
+
+                for (j, r) in index.within(query, cutoff) {
+                    if (j == i && *image == DVec3::ZERO) || r < 1.0e-10 {
+                        continue;
+                    }
+
+                    let x = r / (2.0 * split);
+                    let factor = erfc(x) + 2.0 * x / PI.sqrt() * (-x * x).exp();
+                    let rel = query - positions[j];
+                    accelerations[i] -= rel * (g * masses[j] * factor / (r * r * r));
+                }
+            }
+        }
+    }
+
+    /// Green's function of the mesh, smoothed by a gaussian and divided by the window of the
+    /// assignment twice, for deposit and interpolation. It includes the normalisation of the
+    /// inverse FFT.
+    fn kernel(&self) -> Vec<f64> {
+        let n = self.fft_grid();
+        let frequency = |m: usize| {
+            if m <= n / 2 {
+                m as f64
+            } else {
+                m as f64 - n as f64
+            }
+        };
+        let wavevector = |x: usize, y: usize, z: usize| {
+            DVec3::new(frequency(x), frequency(y), frequency(z)) * (2.0 * PI / n as f64)
+        };
+
+        // Solution of Poisson's equation for a gaussian cloud, without the mean density
+        let smooth = |k: DVec3| {
+            let k_sq = k.length_squared();
+            if k_sq > 0.0 {
+                -4.0 * PI / k_sq * (-k_sq * SPLIT * SPLIT).exp()
+            } else {
+                0.0
+            }
+        };
+
+        let assignment = self.settings.assignment;
+        let deconvolve = |k: DVec3| {
+            let w = assignment.window(0.5 * k.x)
+                * assignment.window(0.5 * k.y)
+                * assignment.window(0.5 * k.z);
+            1.0 / (w * w)
+        };
+
+        let mut kernel = vec![0.0; n * n * n];
+        match self.settings.boundary {
+            // The potential of a gaussian cloud is sampled on the doubled mesh, with distances
+            // wrapped around so the FFT sees a symmetric function. Only its smooth part is
+            // deconvolved, as the kink where distances wrap would be amplified into noise.
+            Boundary::Isolated => {
+                let mut green = vec![Complex::ZERO; n * n * n];
+                for x in 0..n {
+                    for y in 0..n {
+                        for z in 0..n {
+                            let r = DVec3::new(frequency(x), frequency(y), frequency(z)).length();
+                            green[(x * n + y) * n + z].re = if r == 0.0 {
+                                -1.0 / (SPLIT * PI.sqrt())
+                            } else {
+                                -(1.0 - erfc(r / (2.0 * SPLIT))) / r
+                            };
+                        }
+                    }
+                }
+                fft3(&mut green, n, false);
+
+                for x in 0..n {
+                    for y in 0..n {
+                        for z in 0..n {
+                            let i = (x * n + y) * n + z;
+                            let k = wavevector(x, y, z);
+                            kernel[i] = green[i].re + smooth(k) * (deconvolve(k) - 1.0);
+                        }
+                    }
+                }
+            }
+            Boundary::Periodic { .. } => {
+                for x in 0..n {
+                    for y in 0..n {
+                        for z in 0..n {
+                            let k = wavevector(x, y, z);
+                            kernel[(x * n + y) * n + z] = smooth(k) * deconvolve(k);
+                        }
+                    }
+                }
+            }
+        }
+
+        let volume = (n * n * n) as f64;
+        for value in kernel.iter_mut() {
+            *value /= volume;
+        }
+
+        kernel
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    const ZERO: Self = Self { re: 0.0, im: 0.0 };
+
+    fn from_angle(angle: f64) -> Self {
+        Self {
+            re: angle.cos(),
+            im: angle.sin(),
+        }
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            re: self.re + other.re,
+            im: self.im + other.im,
+        }
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            re: self.re - other.re,
+            im: self.im - other.im,
+        }
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+}
+
+impl Mul<f64> for Complex {
+    type Output = Self;
+
+    fn mul(self, scalar: f64) -> Self {
+        Self {
+            re: self.re * scalar,
+            im: self.im * scalar,
+        }
+    }
+}
+
+/// In place radix-2 FFT of a sequence whose length is a power of two. The inverse transform is
+/// not normalised.
+fn fft(data: &mut [Complex], twiddles: &[Complex]) {
+    let n = data.len();
+    let bits = n.trailing_zeros();
+
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let step = n / len;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let a = data[start + k];
+                let b = data[start + k + len / 2] * twiddles[k * step];
+                data[start + k] = a + b;
+                data[start + k + len / 2] = a - b;
+            }
+        }
+        len *= 2;
+    }
+}
+
+/// FFT of an `n`×`n`×`n` mesh, one axis at a time.
+fn fft3(data: &mut [Complex], n: usize, inverse: bool) {
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let twiddles = (0..n / 2)
+        .map(|k| Complex::from_angle(sign * 2.0 * PI * k as f64 / n as f64))
+        .collect::<Vec<_>>();
+
+    let mut line = vec![Complex::ZERO; n];
+    for stride in [n * n, n, 1] {
+        for first in 0..n * n * n {
+            // Each line starts where its index along the axis is zero
+            if (first / stride) % n != 0 {
+                continue;
+            }
+
+            for (i, value) in line.iter_mut().enumerate() {
+                *value = data[first + i * stride];
+            }
+            fft(&mut line, &twiddles);
+            for (i, value) in line.iter().enumerate() {
+                data[first + i * stride] = *value;
+            }
+        }
+    }
+}
+
+/// Complementary error function, with a relative error below 1.2e-7.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let value = t * poly.exp();
+
+    if x >= 0.0 {
+        value
+    } else {
+        2.0 - value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Acceleration of every body by summing over every pair.
+    fn direct(positions: &[DVec3], masses: &[f64]) -> Vec<DVec3> {
+        positions
+            .iter()
+            .map(|pos| {
+                positions
+                    .iter()
+                    .zip(masses)
+                    .filter(|(other, _mass)| *other != pos)
+                    .map(|(other, mass)| {
+                        let rel = *pos - *other;
+                        -rel * (mass / rel.length().powi(3))
+                    })
+                    .fold(DVec3::ZERO, |sum, acc| sum + acc)
+            })
+            .collect()
+    }
+
+    /// Root mean square of the errors relative to the root mean square of the exact values.
+    fn relative_error(found: &[DVec3], exact: &[DVec3]) -> f64 {
+        let error: f64 = found
+            .iter()
+            .zip(exact)
+            .map(|(a, b)| a.distance_squared(*b))
+            .sum();
+        let scale: f64 = exact.iter().map(|a| a.length_squared()).sum();
+        (error / scale).sqrt()
+    }
+
+    fn scattered(count: usize) -> (Vec<DVec3>, Vec<f64>) {
+        let mut seed = 12345u64;
+        let mut next = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 11) as f64 / (1u64 << 53) as f64
+        };
+
+        (0..count)
+            .map(|_| {
+                let pos = DVec3::new(next(), next(), next()) * 10.0 - DVec3::splat(5.0);
+                (pos, 0.5 + next())
+            })
+            .unzip()
+    }
+
+    #[test]
+    fn matches_direct_summation() {
+        let (positions, masses) = scattered(200);
+        let exact = direct(&positions, &masses);
+
+        for assignment in [Assignment::Cic, Assignment::Tsc] {
+            let mesh = ParticleMesh::new(MeshSettings {
+                grid: 32,
+                assignment,
+                boundary: Boundary::Isolated,
+                short_range: true,
+            });
+            let found = mesh.accelerations(&positions, &masses, 1.0);
+            assert!(relative_error(&found, &exact) < 0.01);
+        }
+
+        // Without the short range correction, only bodies far apart keep their force
+        let far = [DVec3::new(-4.0, 0.0, 0.0), DVec3::new(4.0, 1.0, 0.0)];
+        let mesh = ParticleMesh::new(MeshSettings {
+            short_range: false,
+            ..MeshSettings::default()
+        });
+        let found = mesh.accelerations(&far, &[1.0, 2.0], 1.0);
+        assert!(relative_error(&found, &direct(&far, &[1.0, 2.0])) < 0.001);
+
+        // A close pair in a large periodic box barely feels its images
+        let close = [DVec3::new(0.3, 0.0, 0.0), DVec3::new(-0.3, 0.1, 0.0)];
+        let mesh = ParticleMesh::new(MeshSettings {
+            boundary: Boundary::Periodic { size: 40.0 },
+            ..MeshSettings::default()
+        });
+        let found = mesh.accelerations(&close, &[1.0, 1.0], 1.0);
+        assert!(relative_error(&found, &direct(&close, &[1.0, 1.0])) < 0.01);
+    }
+}
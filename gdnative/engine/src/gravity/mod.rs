@@ -1,56 +1,87 @@
-use crate::base::{Root, System, SystemConfig, SystemNode};
+use crate::base::{inspect_subsystems, Inspected};
+use crate::base::{ConfigRegistry, SystemRegistry};
+use crate::base::{RegisteredSystem, Root, Subsystem, System, SystemConfig, SystemNode};
 use crate::global::Units;
-use hecs::{serialize::column::*, Archetype, ColumnBatchBuilder, ColumnBatchType, World};
-use serde::{
-    de::{self, SeqAccess, Visitor},
-    ser::{self, SerializeSeq},
-    Deserialize, Deserializer, Serialize, Serializer,
-};
+use hecs::{serialize::column::*, Archetype, ColumnBatchBuilder, ColumnBatchType, Entity, World};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::any::TypeId;
 
+pub mod blackhole;
+pub mod edit;
+pub mod event;
+pub mod mesh;
 pub mod nbody;
+pub mod trail;
 
 #[derive(Serialize, Deserialize)]
 pub struct GravitationalSystem;
 
 impl System for GravitationalSystem {
     fn solve_begin(&mut self, children: &mut World, config: &SystemConfig, time: f64) {
-        for (_entity, nbody) in children.query_mut::<&mut SystemNode<nbody::NBodySystem>>() {
-            nbody.solve_begin(config, time);
+        for (_entity, subsystem) in children.query_mut::<&mut Subsystem>() {
+            subsystem.get_mut().solve_begin(config, time);
         }
     }
 
     /// Update the system and all subsystems
     fn solve_update(&mut self, children: &mut World, config: &SystemConfig, time: f64, delta: f64) {
-        for (_entity, nbody) in children.query_mut::<&mut SystemNode<nbody::NBodySystem>>() {
-            nbody.solve_update(config, time, delta);
+        for (_entity, subsystem) in children.query_mut::<&mut Subsystem>() {
+            subsystem.get_mut().solve_update(config, time, delta);
         }
     }
 
     fn solve_end(&mut self, children: &mut World, config: &SystemConfig, time: f64) {
-        for (_entity, nbody) in children.query_mut::<&mut SystemNode<nbody::NBodySystem>>() {
-            nbody.solve_end(config, time);
+        for (_entity, subsystem) in children.query_mut::<&mut Subsystem>() {
+            subsystem.get_mut().solve_end(config, time);
         }
     }
 
     fn view_begin(&mut self, children: &mut World, config: &SystemConfig, time: f64) {
-        for (_entity, nbody) in children.query_mut::<&mut SystemNode<nbody::NBodySystem>>() {
-            nbody.view_begin(config, time);
+        for (_entity, subsystem) in children.query_mut::<&mut Subsystem>() {
+            subsystem.get_mut().view_begin(config, time);
         }
     }
 
     fn view_set_time(&mut self, children: &mut World, config: &SystemConfig, time: f64) {
-        for (_entity, nbody) in children.query_mut::<&mut SystemNode<nbody::NBodySystem>>() {
-            nbody.view_set_time(config, time);
+        for (_entity, subsystem) in children.query_mut::<&mut Subsystem>() {
+            subsystem.get_mut().view_set_time(config, time);
         }
     }
 
     fn view_end(&mut self, children: &mut World, config: &SystemConfig, time: f64) {
-        for (_entity, nbody) in children.query_mut::<&mut SystemNode<nbody::NBodySystem>>() {
-            nbody.view_end(config, time);
+        for (_entity, subsystem) in children.query_mut::<&mut Subsystem>() {
+            subsystem.get_mut().view_end(config, time);
         }
     }
 
+    fn edit_begin(&mut self, children: &mut World, config: &SystemConfig) {
+        for (_entity, subsystem) in children.query_mut::<&mut Subsystem>() {
+            subsystem.get_mut().edit_begin(config);
+        }
+    }
+
+    fn edit_end(&mut self, children: &mut World, config: &SystemConfig) {
+        for (_entity, subsystem) in children.query_mut::<&mut Subsystem>() {
+            subsystem.get_mut().edit_end(config);
+        }
+    }
+
+    fn invalidate(&mut self, children: &mut World, config: &SystemConfig) {
+        for (_entity, subsystem) in children.query_mut::<&mut Subsystem>() {
+            subsystem.get_mut().invalidate(config);
+        }
+    }
+
+    fn reset(&mut self, children: &mut World, config: &SystemConfig) {
+        for (_entity, subsystem) in children.query_mut::<&mut Subsystem>() {
+            subsystem.get_mut().reset(config);
+        }
+    }
+
+    fn inspect(&self, children: &World) -> Vec<Inspected> {
+        inspect_subsystems(children)
+    }
+
     fn serialize_system<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
@@ -76,69 +107,135 @@ impl System for GravitationalSystem {
     where
         D: Deserializer<'de>,
     {
-        deserialize(&mut DeContext::default(), deserializer)
+        let mut world: World = deserialize(&mut DeContext::default(), deserializer)?;
+
+        // Files written before subsystems were dynamic store n-body systems directly.
+        let legacy = world
+            .query_mut::<&SystemNode<nbody::NBodySystem>>()
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+
+        for entity in legacy {
+            if let Ok(nbody) = world.remove_one::<SystemNode<nbody::NBodySystem>>(entity) {
+                let _ = world.insert_one(entity, Subsystem::new(nbody));
+            }
+        }
+
+        Ok(world)
     }
 }
 
-impl Root for GravitationalSystem {
-    fn default_config() -> SystemConfig {
-        let mut config = SystemConfig::new();
+impl RegisteredSystem for GravitationalSystem {
+    const KEY: &'static str = "gravitational";
+}
 
-        config.insert(Units::default());
+/// The first n-body subsystem of the root, which bodies are added to and events apply to.
+pub fn first_nbody_mut(
+    root: &mut SystemNode<GravitationalSystem>,
+) -> Option<&mut SystemNode<nbody::NBodySystem>> {
+    root.children_mut()
+        .query_mut::<&mut Subsystem>()
+        .into_iter()
+        .find_map(|(_e, subsystem)| subsystem.downcast_mut::<nbody::NBodySystem>())
+}
 
-        config
-    }
+/// The n-body subsystem held by the `subsystem` entity of the root, if it holds one.
+pub fn nbody_mut(
+    root: &mut SystemNode<GravitationalSystem>,
+    subsystem: Entity,
+) -> Option<&mut SystemNode<nbody::NBodySystem>> {
+    root.children_mut()
+        .query_one_mut::<&mut Subsystem>(subsystem)
+        .ok()?
+        .downcast_mut::<nbody::NBodySystem>()
+}
 
-    fn serialize_config<S>(config: &SystemConfig, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        use ser::Error;
+/// Finds the entity of a subsystem of the root from its id, which is how subsystems are
+/// identified outside of the engine.
+pub fn find_subsystem(root: &SystemNode<GravitationalSystem>, id: u32) -> Option<Entity> {
+    root.children()
+        .query::<&Subsystem>()
+        .iter()
+        .map(|(entity, _subsystem)| entity)
+        .find(|entity| entity.id() == id)
+}
 
-        let mut seq = serializer.serialize_seq(Some(0))?;
-        // seq.serialize_element(
-        //     config
-        //         .get::<Units>()
-        //         .ok_or_else(|| S::Error::custom("config does not contain units"))?,
-        // )?;
-        seq.end()
+/// Identifies a body within a whole tree. Indices of bodies are only unique within their
+/// subsystem, so the id pairs the index with the entity holding the subsystem.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BodyId {
+    pub subsystem: Entity,
+    pub index: usize,
+}
+
+/// The view state of every body in the n-body subsystems of the root, ordered by id. Bodies only
+/// have a view state while the tree is being viewed.
+pub fn view_states(root: &SystemNode<GravitationalSystem>) -> Vec<(BodyId, nbody::ViewState)> {
+    let mut states = Vec::new();
+
+    for (subsystem, nbody) in root.children().query::<&Subsystem>().iter() {
+        let nbody = match nbody.downcast_ref::<nbody::NBodySystem>() {
+            Some(nbody) => nbody,
+            None => continue,
+        };
+
+        for (_e, (body, view)) in nbody
+            .children()
+            .query::<(&nbody::NBody, &nbody::ViewState)>()
+            .iter()
+        {
+            let id = BodyId {
+                subsystem,
+                index: body.index,
+            };
+            states.push((id, view.clone()));
+        }
     }
 
-    fn deserialize_config<'de, D>(deserializer: D) -> Result<SystemConfig, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        struct ConfigDeserializer;
+    states.sort_by_key(|state| state.0);
+    states
+}
 
-        impl<'de> Visitor<'de> for ConfigDeserializer {
-            type Value = SystemConfig;
+/// Registers the systems of this module so they can be loaded as subsystems.
+pub fn register_systems(registry: &mut SystemRegistry) {
+    registry.register::<GravitationalSystem>();
+    registry.register::<nbody::NBodySystem>();
+}
 
-            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                formatter.write_str("SystemConfig sequence.")
-            }
+/// Registers the configs of this module so they are persisted with system trees.
+pub fn register_configs(registry: &mut ConfigRegistry) {
+    registry.register::<nbody::ForceBackend>();
+}
 
-            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
-            where
-                A: SeqAccess<'de>,
-            {
-                use de::Error;
-
-                let mut config = SystemConfig::new();
-                // config.insert::<Units>(
-                //     seq.next_element()?
-                //         .ok_or_else(|| A::Error::custom("config does not contain units"))?,
-                // );
-                Ok(config)
-            }
-        }
+impl Root for GravitationalSystem {
+    type Event = event::GravitationalEvent;
+
+    fn default_config() -> SystemConfig {
+        let mut config = SystemConfig::new();
 
-        deserializer.deserialize_seq(ConfigDeserializer)
+        config.insert(Units::default());
+
+        config
+    }
+
+    /// Events are applied to the first n-body subsystem.
+    fn apply_event(
+        root: &mut SystemNode<Self>,
+        _config: &SystemConfig,
+        time: f64,
+        event: &Self::Event,
+    ) {
+        if let Some(nbody) = first_nbody_mut(root) {
+            event::apply(nbody.children_mut(), time, event);
+        }
     }
 }
 
 #[derive(Serialize, Deserialize)]
 enum ComponentId {
     NBody,
+    Subsystem,
 }
 
 struct SeContext;
@@ -147,7 +244,7 @@ impl SerializeContext for SeContext {
     fn component_count(&self, archetype: &Archetype) -> usize {
         archetype
             .component_types()
-            .filter(|&t| t == TypeId::of::<SystemNode<nbody::NBodySystem>>())
+            .filter(|&t| t == TypeId::of::<Subsystem>())
             .count()
     }
 
@@ -156,11 +253,7 @@ impl SerializeContext for SeContext {
         archetype: &Archetype,
         out: &mut S,
     ) -> Result<(), S::Error> {
-        try_serialize_id::<SystemNode<nbody::NBodySystem>, _, _>(
-            archetype,
-            &ComponentId::NBody,
-            out,
-        )?;
+        try_serialize_id::<Subsystem, _, _>(archetype, &ComponentId::Subsystem, out)?;
         Ok(())
     }
 
@@ -169,7 +262,7 @@ impl SerializeContext for SeContext {
         archetype: &Archetype,
         out: &mut S,
     ) -> Result<(), S::Error> {
-        try_serialize::<SystemNode<nbody::NBodySystem>, _>(archetype, out)?;
+        try_serialize::<Subsystem, _>(archetype, out)?;
         Ok(())
     }
 }
@@ -193,6 +286,9 @@ impl DeserializeContext for DeContext {
                 ComponentId::NBody => {
                     batch.add::<SystemNode<nbody::NBodySystem>>();
                 }
+                ComponentId::Subsystem => {
+                    batch.add::<Subsystem>();
+                }
             }
             self.components.push(id);
         }
@@ -218,8 +314,180 @@ impl DeserializeContext for DeContext {
                         batch,
                     )?;
                 }
+                ComponentId::Subsystem => {
+                    deserialize_column::<Subsystem, _>(entity_count, &mut seq, batch)?;
+                }
             }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::{register_system, SystemTree};
+
+    /// A subsystem the gravitational system knows nothing about.
+    #[derive(Serialize, Deserialize)]
+    struct Counter {
+        updates: usize,
+    }
+
+    impl System for Counter {
+        fn solve_begin(&mut self, _children: &mut World, _config: &SystemConfig, _time: f64) {}
+
+        fn solve_update(
+            &mut self,
+            _children: &mut World,
+            _config: &SystemConfig,
+            _time: f64,
+            _delta: f64,
+        ) {
+            self.updates += 1;
+        }
+
+        fn solve_end(&mut self, _children: &mut World, _config: &SystemConfig, _time: f64) {}
+
+        fn view_begin(&mut self, _children: &mut World, _config: &SystemConfig, _time: f64) {}
+
+        fn view_set_time(&mut self, _children: &mut World, _config: &SystemConfig, _time: f64) {}
+
+        fn view_end(&mut self, _children: &mut World, _config: &SystemConfig, _time: f64) {}
+
+        fn serialize_system<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            self.serialize(serializer)
+        }
+
+        fn deserialize_system<'de, D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Self::deserialize(deserializer)
+        }
+
+        fn serialize_children<S>(_children: &World, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            ().serialize(serializer)
+        }
+
+        fn deserialize_children<'de, D>(deserializer: D) -> Result<World, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            <()>::deserialize(deserializer)?;
+            Ok(World::new())
+        }
+    }
+
+    impl RegisteredSystem for Counter {
+        const KEY: &'static str = "test_counter";
+    }
+
+    #[test]
+    fn heterogeneous_subsystems() {
+        register_system::<Counter>();
+
+        let mut tree = SystemTree::new(GravitationalSystem);
+        tree.root_mut()
+            .children_mut()
+            .spawn((Subsystem::new(SystemNode::new(nbody::NBodySystem)),));
+        tree.root_mut()
+            .children_mut()
+            .spawn((Subsystem::new(SystemNode::new(Counter { updates: 0 })),));
+
+        tree.solve(0.0, 1.0, 9);
+
+        let bytes = bincode::serialize(&tree).unwrap();
+        let loaded: SystemTree<GravitationalSystem> = bincode::deserialize(&bytes).unwrap();
+
+        let mut keys = loaded
+            .root()
+            .children()
+            .query::<&Subsystem>()
+            .iter()
+            .map(|(_e, subsystem)| subsystem.key())
+            .collect::<Vec<_>>();
+        keys.sort_unstable();
+        assert_eq!(keys, vec!["nbody", "test_counter"]);
+
+        let updates = loaded
+            .root()
+            .children()
+            .query::<&Subsystem>()
+            .iter()
+            .find_map(|(_e, subsystem)| {
+                subsystem.downcast_ref::<Counter>().map(|c| c.get().updates)
+            });
+        assert_eq!(updates, Some(10));
+    }
+
+    #[test]
+    fn outline_lists_bodies() {
+        let mut nbodies = SystemNode::new(nbody::NBodySystem);
+        for index in [1, 0] {
+            nbodies.children_mut().spawn((nbody::NBody {
+                index,
+                pos: glam::DVec3::X * index as f64,
+                vel: glam::DVec3::ZERO,
+                mass: 1.0,
+            },));
+        }
+
+        let mut tree = SystemTree::new(GravitationalSystem);
+        tree.root_mut()
+            .children_mut()
+            .spawn((Subsystem::new(nbodies),));
+
+        let outline = tree.root().inspect();
+        assert_eq!(outline.len(), 1);
+        assert_eq!(outline[0].kind, "nbody");
+        assert_eq!(outline[0].child_count, 2);
+
+        let ids = outline[0]
+            .children
+            .iter()
+            .map(|body| body.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![0, 1]);
+        assert_eq!(outline[0].children[1].components[0].name, "NBody");
+    }
+
+    #[test]
+    fn body_ids_are_unique_across_subsystems() {
+        let mut tree = SystemTree::new(GravitationalSystem);
+
+        for x in [1.0, -1.0] {
+            let mut nbodies = SystemNode::new(nbody::NBodySystem);
+            nbodies.children_mut().spawn((nbody::NBody {
+                index: 0,
+                pos: glam::DVec3::X * x,
+                vel: glam::DVec3::ZERO,
+                mass: 1.0,
+            },));
+            tree.root_mut()
+                .children_mut()
+                .spawn((Subsystem::new(nbodies),));
+        }
+
+        tree.view(0.0);
+        let states = view_states(tree.root());
+
+        assert_eq!(states.len(), 2);
+        assert_eq!(states[0].0.index, states[1].0.index);
+        assert_ne!(states[0].0, states[1].0);
+        assert_ne!(states[0].1.pos, states[1].1.pos);
+
+        // The same bodies keep their ids from one view to the next
+        tree.view(0.5);
+        let ids = |states: &[(BodyId, nbody::ViewState)]| {
+            states.iter().map(|(id, _view)| *id).collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&view_states(tree.root())), ids(&states));
+    }
+}
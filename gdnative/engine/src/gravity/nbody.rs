@@ -1,7 +1,12 @@
-use crate::base::{AbstractVector, ContinuousRecord, System, SystemConfig};
+use super::blackhole::{self, Absorbed, BlackHole};
+use super::event::{Despawned, Spawned};
+use super::mesh::ParticleMesh;
+use crate::base::{AbstractVector, ContinuousRecord, RegisteredSystem, System, SystemConfig};
+use crate::base::{Config, Inspected, InspectedComponent, PersistentConfig, Value};
 use crate::global::Units;
 use gdnative::core_types::Rid;
 use glam::DVec3;
+use hashbrown::HashMap;
 use hecs::{serialize::column::*, Archetype, ColumnBatchBuilder, ColumnBatchType, World};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::any::TypeId;
@@ -28,6 +33,28 @@ impl AbstractVector for Position {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Velocity {
+    pub vel: DVec3,
+}
+
+impl AbstractVector for Velocity {
+    fn zero() -> Self {
+        Self { vel: DVec3::ZERO }
+    }
+
+    fn one() -> Self {
+        Self { vel: DVec3::ONE }
+    }
+
+    fn add(&mut self, other: Self) {
+        self.vel += other.vel;
+    }
+    fn scale(&mut self, scalar: f64) {
+        self.vel *= scalar;
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct NBody {
     pub index: usize,
@@ -36,10 +63,209 @@ pub struct NBody {
     pub mass: f64,
 }
 
+/// The state a body was in when it began to be solved, which the body returns to when its system
+/// is reset.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InitialState(pub NBody);
+
+/// Visual properties of a body which is a star.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Star {
+    pub temperature: f64,
+}
+
+/// A name given to a body by the user.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Name {
+    pub name: String,
+}
+
+/// The state of a body at the time being viewed. It is written by the view lifecycle of
+/// `NBodySystem`, and never saved.
+#[derive(Clone, Debug, Default)]
+pub struct ViewState {
+    pub time: f64,
+    pub pos: DVec3,
+    pub vel: DVec3,
+    pub mass: f64,
+    /// Radius of the event horizon, or zero for bodies which are not black holes
+    pub horizon_radius: f64,
+    /// Whether the body has records at the viewed time
+    pub visible: bool,
+}
+
+/// Saves the current state of every simulated body.
+pub(crate) fn save_records(children: &mut World, time: f64) {
+    for (_entity, (body, positions, velocities)) in children
+        .query_mut::<(
+            &NBody,
+            &mut ContinuousRecord<Position>,
+            Option<&mut ContinuousRecord<Velocity>>,
+        )>()
+        .without::<Absorbed>()
+        .without::<Despawned>()
+    {
+        positions.save(time, Position { pos: body.pos });
+
+        if let Some(velocities) = velocities {
+            velocities.save(time, Velocity { vel: body.vel });
+        }
+    }
+}
+
+/// Acceleration of `orbiting` due to the gravity of `bodies`.
+fn acceleration(orbiting: &NBody, bodies: &[NBody], g: f64) -> DVec3 {
+    let mut acc = DVec3::new(0.0, 0.0, 0.0);
+
+    for grav in bodies.iter() {
+        let rel_pos = orbiting.pos - grav.pos;
+        let r = rel_pos.length();
+
+        if r < 1.0e-10 {
+            continue;
+        }
+
+        let rel_vel = orbiting.vel - grav.vel;
+        let rel_vel_sq = DVec3::new(
+            rel_vel.x * rel_vel.x,
+            rel_vel.y * rel_vel.y,
+            rel_vel.z * rel_vel.z,
+        );
+        let rel_vel_pos = DVec3::new(
+            rel_pos.x * rel_vel.x,
+            rel_pos.y * rel_vel.y,
+            rel_pos.z * rel_vel.z,
+        );
+
+        let mu = g * grav.mass;
+
+        let force_over_r = -mu / (r * r * r);
+        acc.x += force_over_r * rel_pos.x;
+        acc.y += force_over_r * rel_pos.y;
+        acc.z += force_over_r * rel_pos.z;
+
+        // let m = mu / (2.0 * c_sq * r);
+        // let one_over_c_sq_one_plus_m = 1.0 / (c_sq * (1.0 + m));
+        // let one_minus_m_over_one_plus_m = (1.0 - m)
+        //     / ((1.0 + m)
+        //         * (1.0 + m)
+        //         * (1.0 + m)
+        //         * (1.0 + m)
+        //         * (1.0 + m)
+        //         * (1.0 + m)
+        //         * (1.0 + m));
+        // let rel_pos_dot_vel_over_one_minus_m =
+        //     (rel_vel_pos.x + rel_vel_pos.y + rel_vel_pos.z) / (1.0 - m);
+
+        // acc.x += force_over_r
+        //     * (one_minus_m_over_one_plus_m * rel_pos.x
+        //         - one_over_c_sq_one_plus_m
+        //             * (rel_pos.x * (rel_vel_sq.x - rel_vel_sq.y - rel_vel_sq.z)
+        //                 + 2.0
+        //                     * rel_vel.x
+        //                     * (rel_vel_pos.y
+        //                         + rel_vel_pos.z
+        //                         + rel_pos_dot_vel_over_one_minus_m)));
+
+        // acc.y += force_over_r
+        //     * (one_minus_m_over_one_plus_m * rel_pos.y
+        //         - one_over_c_sq_one_plus_m
+        //             * (rel_pos.y * (rel_vel_sq.y - rel_vel_sq.x - rel_vel_sq.z)
+        //                 + 2.0
+        //                     * rel_vel.y
+        //                     * (rel_vel_pos.x
+        //                         + rel_vel_pos.z
+        //                         + rel_pos_dot_vel_over_one_minus_m)));
+
+        // acc.z += force_over_r
+        //     * (one_minus_m_over_one_plus_m * rel_pos.z
+        //         - one_over_c_sq_one_plus_m
+        //             * (rel_pos.z * (rel_vel_sq.z - rel_vel_sq.y - rel_vel_sq.x)
+        //                 + 2.0
+        //                     * rel_vel.z
+        //                     * (rel_vel_pos.y
+        //                         + rel_vel_pos.x
+        //                         + rel_pos_dot_vel_over_one_minus_m)));
+    }
+
+    acc
+}
+
+/// How gravity between bodies is computed. Trees without this config use direct summation.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub enum ForceBackend {
+    /// Sums the force of every body on every other body
+    #[default]
+    Direct,
+    /// Solves for the potential on a mesh, which scales to far more bodies at some cost in
+    /// accuracy
+    ParticleMesh(ParticleMesh),
+}
+
+impl ForceBackend {
+    /// Acceleration of every body due to the gravity of the others.
+    pub fn accelerations(&self, bodies: &[NBody], g: f64) -> Vec<DVec3> {
+        match self {
+            Self::Direct => bodies
+                .iter()
+                .map(|body| acceleration(body, bodies, g))
+                .collect(),
+            Self::ParticleMesh(mesh) => {
+                let positions = bodies.iter().map(|body| body.pos).collect::<Vec<_>>();
+                let masses = bodies.iter().map(|body| body.mass).collect::<Vec<_>>();
+                mesh.accelerations(&positions, &masses, g)
+            }
+        }
+    }
+}
+
+impl Config for ForceBackend {}
+
+impl PersistentConfig for ForceBackend {
+    const KEY: &'static str = "force_backend";
+}
+
+/// Constants the bodies move under, in the units of their positions, velocities and masses. Both
+/// the integrator and black hole horizons take them from `of`, so they always follow the units of
+/// the tree.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PhysicalConstants {
+    pub gravitational: f64,
+    pub speed_of_light: f64,
+}
+
+impl PhysicalConstants {
+    /// Units in which both the gravitational constant and the speed of light are 1
+    pub const NATURAL: Self = Self {
+        gravitational: 1.0,
+        speed_of_light: 1.0,
+    };
+
+    pub fn in_units(units: &Units) -> Self {
+        Self {
+            gravitational: units.gravitational_constant(),
+            speed_of_light: units.speed_of_light(),
+        }
+    }
+
+    /// The constants in the units of a tree, or `NATURAL` for trees without units.
+    pub fn of(config: &SystemConfig) -> Self {
+        config.get::<Units>().map_or(Self::NATURAL, Self::in_units)
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 enum ComponentId {
     Body,
     Record,
+    BlackHole,
+    Absorbed,
+    Despawned,
+    VelocityRecord,
+    Star,
+    Name,
+    InitialState,
+    Spawned,
 }
 
 struct SeContext;
@@ -49,7 +275,16 @@ impl SerializeContext for SeContext {
         archetype
             .component_types()
             .filter(|&t| {
-                t == TypeId::of::<NBody>() || t == TypeId::of::<ContinuousRecord<Position>>()
+                t == TypeId::of::<NBody>()
+                    || t == TypeId::of::<ContinuousRecord<Position>>()
+                    || t == TypeId::of::<BlackHole>()
+                    || t == TypeId::of::<Absorbed>()
+                    || t == TypeId::of::<Despawned>()
+                    || t == TypeId::of::<ContinuousRecord<Velocity>>()
+                    || t == TypeId::of::<Star>()
+                    || t == TypeId::of::<Name>()
+                    || t == TypeId::of::<InitialState>()
+                    || t == TypeId::of::<Spawned>()
             })
             .count()
     }
@@ -61,6 +296,18 @@ impl SerializeContext for SeContext {
     ) -> Result<(), S::Error> {
         try_serialize_id::<NBody, _, _>(archetype, &ComponentId::Body, out)?;
         try_serialize_id::<ContinuousRecord<Position>, _, _>(archetype, &ComponentId::Record, out)?;
+        try_serialize_id::<BlackHole, _, _>(archetype, &ComponentId::BlackHole, out)?;
+        try_serialize_id::<Absorbed, _, _>(archetype, &ComponentId::Absorbed, out)?;
+        try_serialize_id::<Despawned, _, _>(archetype, &ComponentId::Despawned, out)?;
+        try_serialize_id::<ContinuousRecord<Velocity>, _, _>(
+            archetype,
+            &ComponentId::VelocityRecord,
+            out,
+        )?;
+        try_serialize_id::<Star, _, _>(archetype, &ComponentId::Star, out)?;
+        try_serialize_id::<Name, _, _>(archetype, &ComponentId::Name, out)?;
+        try_serialize_id::<InitialState, _, _>(archetype, &ComponentId::InitialState, out)?;
+        try_serialize_id::<Spawned, _, _>(archetype, &ComponentId::Spawned, out)?;
         Ok(())
     }
 
@@ -71,6 +318,14 @@ impl SerializeContext for SeContext {
     ) -> Result<(), S::Error> {
         try_serialize::<NBody, _>(archetype, out)?;
         try_serialize::<ContinuousRecord<Position>, _>(archetype, out)?;
+        try_serialize::<BlackHole, _>(archetype, out)?;
+        try_serialize::<Absorbed, _>(archetype, out)?;
+        try_serialize::<Despawned, _>(archetype, out)?;
+        try_serialize::<ContinuousRecord<Velocity>, _>(archetype, out)?;
+        try_serialize::<Star, _>(archetype, out)?;
+        try_serialize::<Name, _>(archetype, out)?;
+        try_serialize::<InitialState, _>(archetype, out)?;
+        try_serialize::<Spawned, _>(archetype, out)?;
         Ok(())
     }
 }
@@ -97,6 +352,30 @@ impl DeserializeContext for DeContext {
                 ComponentId::Record => {
                     batch.add::<ContinuousRecord<Position>>();
                 }
+                ComponentId::BlackHole => {
+                    batch.add::<BlackHole>();
+                }
+                ComponentId::Absorbed => {
+                    batch.add::<Absorbed>();
+                }
+                ComponentId::Despawned => {
+                    batch.add::<Despawned>();
+                }
+                ComponentId::VelocityRecord => {
+                    batch.add::<ContinuousRecord<Velocity>>();
+                }
+                ComponentId::Star => {
+                    batch.add::<Star>();
+                }
+                ComponentId::Name => {
+                    batch.add::<Name>();
+                }
+                ComponentId::InitialState => {
+                    batch.add::<InitialState>();
+                }
+                ComponentId::Spawned => {
+                    batch.add::<Spawned>();
+                }
             }
             self.components.push(id);
         }
@@ -125,6 +404,34 @@ impl DeserializeContext for DeContext {
                         batch,
                     )?;
                 }
+                ComponentId::BlackHole => {
+                    deserialize_column::<BlackHole, _>(entity_count, &mut seq, batch)?;
+                }
+                ComponentId::Absorbed => {
+                    deserialize_column::<Absorbed, _>(entity_count, &mut seq, batch)?;
+                }
+                ComponentId::Despawned => {
+                    deserialize_column::<Despawned, _>(entity_count, &mut seq, batch)?;
+                }
+                ComponentId::VelocityRecord => {
+                    deserialize_column::<ContinuousRecord<Velocity>, _>(
+                        entity_count,
+                        &mut seq,
+                        batch,
+                    )?;
+                }
+                ComponentId::Star => {
+                    deserialize_column::<Star, _>(entity_count, &mut seq, batch)?;
+                }
+                ComponentId::Name => {
+                    deserialize_column::<Name, _>(entity_count, &mut seq, batch)?;
+                }
+                ComponentId::InitialState => {
+                    deserialize_column::<InitialState, _>(entity_count, &mut seq, batch)?;
+                }
+                ComponentId::Spawned => {
+                    deserialize_column::<Spawned, _>(entity_count, &mut seq, batch)?;
+                }
             }
         }
         Ok(())
@@ -135,138 +442,268 @@ impl DeserializeContext for DeContext {
 pub struct NBodySystem;
 
 impl System for NBodySystem {
-    fn solve_begin(&mut self, _children: &mut World, _config: &SystemConfig, _time: f64) {}
+    /// Remembers the state of bodies which have not been solved yet, so `reset` can return to
+    /// it. Bodies from files written before velocities were recorded start recording them now.
+    fn solve_begin(&mut self, children: &mut World, _config: &SystemConfig, _time: f64) {
+        let initial = children
+            .query_mut::<&NBody>()
+            .without::<InitialState>()
+            .without::<Spawned>()
+            .into_iter()
+            .map(|(entity, body)| (entity, InitialState(body.clone())))
+            .collect::<Vec<_>>();
+
+        for (entity, state) in initial {
+            let _ = children.insert_one(entity, state);
+        }
+
+        let missing = children
+            .query_mut::<&NBody>()
+            .with::<ContinuousRecord<Position>>()
+            .without::<ContinuousRecord<Velocity>>()
+            .into_iter()
+            .map(|(entity, _body)| entity)
+            .collect::<Vec<_>>();
+
+        for entity in missing {
+            let _ = children.insert_one(entity, ContinuousRecord::<Velocity>::new());
+        }
+    }
 
     /// Update the system and all subsystems
-    fn solve_update(
-        &mut self,
-        children: &mut World,
-        _config: &SystemConfig,
-        time: f64,
-        delta: f64,
-    ) {
-        // let units = config.get::<Units>().unwrap();
-
-        for (_entity, (star, record)) in
-            children.query_mut::<(&NBody, &mut ContinuousRecord<Position>)>()
-        {
-            record.save(
-                time,
-                Position {
-                    pos: star.pos.clone(),
-                },
-            )
+    fn solve_update(&mut self, children: &mut World, config: &SystemConfig, time: f64, delta: f64) {
+        save_records(children, time);
+
+        let constants = PhysicalConstants::of(config);
+        let g = constants.gravitational;
+
+        let mut bodies = children
+            .query_mut::<&NBody>()
+            .without::<Absorbed>()
+            .without::<Despawned>()
+            .into_iter()
+            .map(|(entity, body)| (entity, body.clone()))
+            .collect::<Vec<_>>();
+
+        // Where each body started, so absorption can follow it along the whole step
+        let start = bodies
+            .iter()
+            .map(|(entity, body)| (*entity, (body.pos, body.vel)))
+            .collect::<HashMap<_, _>>();
+
+        let direct = ForceBackend::Direct;
+        let backend = config.get::<ForceBackend>().unwrap_or(&direct);
+
+        // Kick-drift-kick leapfrog, which is time reversible, so solving with a negative delta
+        // retraces a forward solve.
+        let snapshot = bodies
+            .iter()
+            .map(|(_e, body)| body.clone())
+            .collect::<Vec<_>>();
+        let accelerations = backend.accelerations(&snapshot, g);
+        for ((_e, body), acc) in bodies.iter_mut().zip(accelerations) {
+            body.vel += acc * (0.5 * delta);
+            body.pos += body.vel * delta;
         }
 
-        // let c = units.speed_of_light();
-        // let g = units.gravitational_constant();
-        let c = 1.0;
-        let g = 1.0;
-        let c_sq = c * c;
+        let snapshot = bodies
+            .iter()
+            .map(|(_e, body)| body.clone())
+            .collect::<Vec<_>>();
+        let accelerations = backend.accelerations(&snapshot, g);
+        for ((_e, body), acc) in bodies.iter_mut().zip(accelerations) {
+            body.vel += acc * (0.5 * delta);
+        }
+
+        for (entity, body) in bodies {
+            if let Ok(current) = children.query_one_mut::<&mut NBody>(entity) {
+                *current = body;
+            }
+        }
+
+        blackhole::absorb(children, &constants, time, delta, &start);
+    }
+
+    fn solve_end(&mut self, children: &mut World, _config: &SystemConfig, time: f64) {
+        save_records(children, time);
+    }
 
-        let nbodystars = children
+    /// Gives every body a `ViewState`, then writes the state at `time` into it. Calling it again
+    /// picks up bodies added since the view began.
+    fn view_begin(&mut self, children: &mut World, config: &SystemConfig, time: f64) {
+        let missing = children
             .query_mut::<&NBody>()
+            .without::<ViewState>()
             .into_iter()
-            .map(|(_e, star)| star.clone())
+            .map(|(entity, _body)| entity)
             .collect::<Vec<_>>();
 
-        for (_entity, orbiting) in children.query_mut::<&mut NBody>() {
-            let mut acc = DVec3::new(0.0, 0.0, 0.0);
+        for entity in missing {
+            let _ = children.insert_one(entity, ViewState::default());
+        }
 
-            for grav in nbodystars.iter() {
-                let rel_pos = orbiting.pos - grav.pos;
-                let r = rel_pos.length();
+        self.view_set_time(children, config, time);
+    }
 
-                if r < 1.0e-10 {
-                    continue;
+    fn view_set_time(&mut self, children: &mut World, config: &SystemConfig, time: f64) {
+        let constants = PhysicalConstants::of(config);
+
+        for (_entity, (body, positions, velocities, hole, view)) in children.query_mut::<(
+            &NBody,
+            Option<&ContinuousRecord<Position>>,
+            Option<&ContinuousRecord<Velocity>>,
+            Option<&BlackHole>,
+            &mut ViewState,
+        )>() {
+            view.time = time;
+
+            // Bodies which have never been solved are shown as they are.
+            let (pos, vel, visible) = match positions {
+                Some(positions) if positions.span().is_some() => (
+                    positions.load(time).pos,
+                    velocities.map_or(DVec3::ZERO, |velocities| velocities.load(time).vel),
+                    positions.contains(time),
+                ),
+                _ => (body.pos, body.vel, true),
+            };
+
+            view.pos = pos;
+            view.vel = vel;
+            view.visible = visible;
+
+            match hole {
+                Some(hole) => {
+                    view.mass = hole.mass_at(body.mass, time);
+                    view.horizon_radius = hole.horizon_radius_at(body.mass, &constants, time);
+                }
+                None => {
+                    view.mass = body.mass;
+                    view.horizon_radius = 0.0;
                 }
+            }
+        }
+    }
 
-                let rel_vel = orbiting.vel - grav.vel;
-                let rel_vel_sq = DVec3::new(
-                    rel_vel.x * rel_vel.x,
-                    rel_vel.y * rel_vel.y,
-                    rel_vel.z * rel_vel.z,
-                );
-                let rel_vel_pos = DVec3::new(
-                    rel_pos.x * rel_vel.x,
-                    rel_pos.y * rel_vel.y,
-                    rel_pos.z * rel_vel.z,
-                );
+    fn view_end(&mut self, children: &mut World, _config: &SystemConfig, _time: f64) {
+        let viewed = children
+            .query_mut::<&ViewState>()
+            .into_iter()
+            .map(|(entity, _view)| entity)
+            .collect::<Vec<_>>();
 
-                let mu = g * grav.mass;
-
-                let force_over_r = -mu / (r * r * r);
-                acc.x += force_over_r * rel_pos.x;
-                acc.y += force_over_r * rel_pos.y;
-                acc.z += force_over_r * rel_pos.z;
-
-                // let m = mu / (2.0 * c_sq * r);
-                // let one_over_c_sq_one_plus_m = 1.0 / (c_sq * (1.0 + m));
-                // let one_minus_m_over_one_plus_m = (1.0 - m)
-                //     / ((1.0 + m)
-                //         * (1.0 + m)
-                //         * (1.0 + m)
-                //         * (1.0 + m)
-                //         * (1.0 + m)
-                //         * (1.0 + m)
-                //         * (1.0 + m));
-                // let rel_pos_dot_vel_over_one_minus_m =
-                //     (rel_vel_pos.x + rel_vel_pos.y + rel_vel_pos.z) / (1.0 - m);
-
-                // acc.x += force_over_r
-                //     * (one_minus_m_over_one_plus_m * rel_pos.x
-                //         - one_over_c_sq_one_plus_m
-                //             * (rel_pos.x * (rel_vel_sq.x - rel_vel_sq.y - rel_vel_sq.z)
-                //                 + 2.0
-                //                     * rel_vel.x
-                //                     * (rel_vel_pos.y
-                //                         + rel_vel_pos.z
-                //                         + rel_pos_dot_vel_over_one_minus_m)));
-
-                // acc.y += force_over_r
-                //     * (one_minus_m_over_one_plus_m * rel_pos.y
-                //         - one_over_c_sq_one_plus_m
-                //             * (rel_pos.y * (rel_vel_sq.y - rel_vel_sq.x - rel_vel_sq.z)
-                //                 + 2.0
-                //                     * rel_vel.y
-                //                     * (rel_vel_pos.x
-                //                         + rel_vel_pos.z
-                //                         + rel_pos_dot_vel_over_one_minus_m)));
-
-                // acc.z += force_over_r
-                //     * (one_minus_m_over_one_plus_m * rel_pos.z
-                //         - one_over_c_sq_one_plus_m
-                //             * (rel_pos.z * (rel_vel_sq.z - rel_vel_sq.y - rel_vel_sq.x)
-                //                 + 2.0
-                //                     * rel_vel.z
-                //                     * (rel_vel_pos.y
-                //                         + rel_vel_pos.x
-                //                         + rel_pos_dot_vel_over_one_minus_m)));
-            }
+        for entity in viewed {
+            let _ = children.remove_one::<ViewState>(entity);
+        }
+    }
+
+    fn invalidate(&mut self, children: &mut World, _config: &SystemConfig) {
+        for (_entity, record) in children.query_mut::<&mut ContinuousRecord<Position>>() {
+            *record = ContinuousRecord::new();
+        }
 
-            orbiting.vel += acc * delta;
-            orbiting.pos += orbiting.vel * delta;
+        for (_entity, record) in children.query_mut::<&mut ContinuousRecord<Velocity>>() {
+            *record = ContinuousRecord::new();
         }
     }
 
-    fn solve_end(&mut self, children: &mut World, _config: &SystemConfig, time: f64) {
-        for (_entity, (star, record)) in
-            children.query_mut::<(&NBody, &mut ContinuousRecord<Position>)>()
-        {
-            record.save(
-                time,
-                Position {
-                    pos: star.pos.clone(),
-                },
-            )
+    /// Removes bodies added by events and returns the others to their initial state, as neither
+    /// absorbed nor despawned.
+    fn reset(&mut self, children: &mut World, _config: &SystemConfig) {
+        let spawned = children
+            .query_mut::<()>()
+            .with::<Spawned>()
+            .into_iter()
+            .map(|(entity, ())| entity)
+            .collect::<Vec<_>>();
+
+        for entity in spawned {
+            let _ = children.despawn(entity);
+        }
+
+        let solved = children
+            .query_mut::<(&mut NBody, &InitialState)>()
+            .into_iter()
+            .map(|(entity, (body, initial))| {
+                *body = initial.0.clone();
+                entity
+            })
+            .collect::<Vec<_>>();
+
+        for entity in solved {
+            let _ = children.remove_one::<InitialState>(entity);
+            let _ = children.remove_one::<Absorbed>(entity);
+            let _ = children.remove_one::<Despawned>(entity);
+        }
+
+        for (_entity, hole) in children.query_mut::<&mut BlackHole>() {
+            hole.absorptions.clear();
         }
     }
 
-    fn view_begin(&mut self, _children: &mut World, _config: &SystemConfig, _time: f64) {}
+    /// Bodies are identified by their index.
+    fn inspect(&self, children: &World) -> Vec<Inspected> {
+        let mut items = Vec::new();
+
+        for (entity, body) in children.query::<&NBody>().iter() {
+            let mut item = Inspected::new(body.index as u64, "body");
+
+            if let Ok(name) = children.get::<Name>(entity) {
+                item.components.push(
+                    InspectedComponent::new("Name").with("name", Value::Text(name.name.clone())),
+                );
+            }
+
+            item.components.push(
+                InspectedComponent::new("NBody")
+                    .with("position", Value::Vector(body.pos))
+                    .with("velocity", Value::Vector(body.vel))
+                    .with("mass", Value::Float(body.mass)),
+            );
+
+            if let Ok(star) = children.get::<Star>(entity) {
+                item.components.push(
+                    InspectedComponent::new("Star")
+                        .with("temperature", Value::Float(star.temperature)),
+                );
+            }
+
+            if let Ok(hole) = children.get::<BlackHole>(entity) {
+                item.components.push(
+                    InspectedComponent::new("BlackHole")
+                        .with("absorptions", Value::Int(hole.absorptions.len() as i64)),
+                );
+            }
+
+            if let Ok(absorbed) = children.get::<Absorbed>(entity) {
+                item.components.push(
+                    InspectedComponent::new("Absorbed")
+                        .with("time", Value::Float(absorbed.time))
+                        .with("by", Value::Int(absorbed.by as i64)),
+                );
+            }
+
+            if let Ok(despawned) = children.get::<Despawned>(entity) {
+                item.components.push(
+                    InspectedComponent::new("Despawned").with("time", Value::Float(despawned.time)),
+                );
+            }
+
+            if let Ok(record) = children.get::<ContinuousRecord<Position>>(entity) {
+                let mut component = InspectedComponent::new("PositionRecord");
+                if let Some((begin, end)) = record.span() {
+                    component = component
+                        .with("begin", Value::Float(begin))
+                        .with("end", Value::Float(end));
+                }
+                item.components.push(component);
+            }
 
-    fn view_set_time(&mut self, _children: &mut World, _config: &SystemConfig, _time: f64) {}
+            items.push(item);
+        }
 
-    fn view_end(&mut self, _children: &mut World, _config: &SystemConfig, _time: f64) {}
+        items.sort_by_key(|item| item.id);
+        items
+    }
 
     fn serialize_system<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -296,3 +733,7 @@ impl System for NBodySystem {
         deserialize(&mut DeContext::default(), deserializer)
     }
 }
+
+impl RegisteredSystem for NBodySystem {
+    const KEY: &'static str = "nbody";
+}
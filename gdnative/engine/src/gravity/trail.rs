@@ -0,0 +1,167 @@
+use super::nbody::Position;
+use crate::base::ContinuousRecord;
+use glam::DVec3;
+use std::collections::BinaryHeap;
+
+/// A point of a trail, along with the time the body passed it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrailPoint {
+    pub time: f64,
+    pub pos: DVec3,
+}
+
+/// A run of samples between two kept points, ordered by how far the worst sample strays from the
+/// straight line between them.
+struct Span {
+    deviation: f64,
+    /// Sample furthest from the line
+    split: usize,
+    first: usize,
+    last: usize,
+}
+
+impl PartialEq for Span {
+    fn eq(&self, other: &Self) -> bool {
+        self.deviation == other.deviation
+    }
+}
+
+impl Eq for Span {}
+
+impl PartialOrd for Span {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Span {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.deviation.total_cmp(&other.deviation)
+    }
+}
+
+/// Samples the path of a body between `start` and `end` as a polyline of at most `max_points`
+/// points. Points are added where the path bends the most, so straight stretches take few points
+/// and close passes keep their detail. Points are ordered by increasing time.
+pub fn trail(
+    record: &ContinuousRecord<Position>,
+    start: f64,
+    end: f64,
+    max_points: usize,
+) -> Vec<TrailPoint> {
+    let samples = record
+        .samples(start, end)
+        .into_iter()
+        .map(|(time, value)| TrailPoint {
+            time,
+            pos: value.pos,
+        })
+        .collect::<Vec<_>>();
+
+    if samples.len() <= max_points.max(2) {
+        return samples;
+    }
+
+    let origin = samples[0].pos;
+    let extent = samples
+        .iter()
+        .map(|sample| sample.pos.distance(origin))
+        .fold(0.0, f64::max);
+    let tolerance = extent * 1e-9;
+
+    let mut keep = vec![false; samples.len()];
+    keep[0] = true;
+    keep[samples.len() - 1] = true;
+
+    let mut kept = 2;
+    let mut spans = BinaryHeap::new();
+    spans.extend(span(&samples, 0, samples.len() - 1));
+
+    while kept < max_points {
+        let worst = match spans.pop() {
+            Some(worst) if worst.deviation > tolerance => worst,
+            _ => break,
+        };
+
+        keep[worst.split] = true;
+        kept += 1;
+
+        spans.extend(span(&samples, worst.first, worst.split));
+        spans.extend(span(&samples, worst.split, worst.last));
+    }
+
+    samples
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(sample, keep)| keep.then_some(sample))
+        .collect()
+}
+
+/// Finds the sample between `first` and `last` furthest from the line joining them.
+fn span(samples: &[TrailPoint], first: usize, last: usize) -> Option<Span> {
+    let a = samples[first].pos;
+    let ab = samples[last].pos - a;
+    let length_sq = ab.length_squared();
+
+    (first + 1..last)
+        .map(|i| {
+            let ap = samples[i].pos - a;
+            let deviation = if length_sq > 0.0 {
+                (ap - ab * (ap.dot(ab) / length_sq)).length()
+            } else {
+                ap.length()
+            };
+            (i, deviation)
+        })
+        .max_by(|x, y| x.1.total_cmp(&y.1))
+        .map(|(split, deviation)| Span {
+            deviation,
+            split,
+            first,
+            last,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bends_keep_detail() {
+        let mut record = ContinuousRecord::<Position>::new();
+
+        // A straight run followed by a half circle
+        for i in 0..=100 {
+            let t = i as f64;
+            record.save(
+                t,
+                Position {
+                    pos: DVec3::new(t - 100.0, 0.0, 0.0),
+                },
+            );
+        }
+        for i in 1..=100 {
+            let angle = std::f64::consts::PI * i as f64 / 100.0;
+            record.save(
+                100.0 + i as f64,
+                Position {
+                    pos: DVec3::new(angle.sin() * 10.0, 10.0 - angle.cos() * 10.0, 0.0),
+                },
+            );
+        }
+
+        let points = trail(&record, 50.5, 200.0, 20);
+
+        assert_eq!(points.len(), 20);
+        assert_eq!(points[0].time, 50.5);
+        assert_eq!(points[0].pos, DVec3::new(-49.5, 0.0, 0.0));
+        assert_eq!(points.last().unwrap().time, 200.0);
+        assert!(points.windows(2).all(|w| w[0].time < w[1].time));
+
+        // The straight run needs no points between its ends
+        let straight = points.iter().filter(|p| p.time < 100.0).count();
+        assert_eq!(straight, 1);
+
+        assert!(trail(&record, 300.0, 400.0, 20).is_empty());
+    }
+}
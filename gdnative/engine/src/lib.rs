@@ -1,6 +1,8 @@
 pub mod base;
+pub mod field;
 pub mod global;
 pub mod gravity;
+pub mod new;
 pub mod scripts;
 
 #[cfg(test)]
@@ -56,7 +58,9 @@ mod tests {
             ContinuousRecord::<Position>::new(),
         ));
 
-        tree.root_mut().children_mut().spawn((nbodies,));
+        tree.root_mut()
+            .children_mut()
+            .spawn((Subsystem::new(nbodies),));
 
         tree.solve(0.0, 50.0, 10000);
 
@@ -1,5 +1,5 @@
 use hashbrown::{hash_map::DefaultHashBuilder, HashMap};
-use hecs::{Entity, World};
+use hecs::{Entity, Ref, World};
 use std::any::Any;
 use std::any::TypeId;
 use std::hash::Hash;
@@ -12,12 +12,16 @@ pub enum SystemError {
     InvalidSystemId,
     #[error("Root System")]
     RootSystem,
+    #[error("System is not of the requested type")]
+    TypeMismatch,
+    #[error("A system can not be moved beneath itself")]
+    CyclicHierarchy,
 }
 
 pub trait System: Send + Sync + Any {
-    fn on_create(&mut self, manager: SystemManager, time: f64);
-    fn on_update(&mut self, manager: SystemManager, time: f64, delta: f64);
-    fn on_destroy(&mut self, manager: SystemManager, time: f64);
+    fn on_create(&mut self, manager: &mut SystemManager, time: f64);
+    fn on_update(&mut self, manager: &mut SystemManager, time: f64, delta: f64);
+    fn on_destroy(&mut self, manager: &mut SystemManager, time: f64);
 
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
@@ -34,22 +38,29 @@ pub trait Context<R: Root + System>: Send + Sync + Any {
     fn on_attach(&mut self, root: &R);
     fn on_detach(&mut self);
 
-    fn on_edit_begin(&self, manager: SystemManager, system: SystemId);
-    fn on_edit_end(&self, maanger: SystemManager, system: SystemId);
+    /// Called before `system` (or its list of children) is modified.
+    fn on_edit_begin(&mut self, manager: &SystemManager, system: SystemId);
+    /// Called once the modification of `system` has completed.
+    fn on_edit_end(&mut self, manager: &SystemManager, system: SystemId);
 
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
-#[derive(Clone, Copy, Hash, PartialEq, Eq)]
-pub struct SystemId(usize);
+/// Identifies a system within a tree. Slots of removed systems are reused under a new
+/// generation, so ids of removed systems stay invalid.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct SystemId {
+    index: usize,
+    generation: u32,
+}
 
 pub struct SystemManager {
-    systems: HashMap<SystemId, SystemNode>,
+    systems: HashMap<usize, SystemNode>,
     config: TypeIdMap<Box<dyn Config>>,
 
     free_list: Vec<SystemId>,
-    next_id: SystemId,
+    next_index: usize,
 }
 
 impl SystemManager {
@@ -71,29 +82,71 @@ impl SystemManager {
         config.as_any_mut().downcast_mut().unwrap()
     }
 
+    pub fn contains(&self, id: SystemId) -> bool {
+        self.node(id).is_ok()
+    }
+
     pub fn entity(&self, id: SystemId) -> Result<Entity, SystemError> {
-        Ok(self
-            .systems
-            .get(&id)
-            .ok_or(SystemError::InvalidSystemId)?
-            .entity
-            .ok_or(SystemError::RootSystem)?)
+        self.node(id)?.entity.ok_or(SystemError::RootSystem)
     }
 
+    pub fn parent(&self, id: SystemId) -> Result<SystemId, SystemError> {
+        let node = self.node(id)?;
+        node.entity.ok_or(SystemError::RootSystem)?;
+        Ok(node.parent)
+    }
+
+    /// The world containing the objects and subsystems of a system.
     pub fn children(&self, id: SystemId) -> Result<&World, SystemError> {
-        Ok(&self
-            .systems
-            .get(&id)
-            .ok_or(SystemError::InvalidSystemId)?
-            .children)
+        Ok(&self.node(id)?.children)
+    }
+
+    pub fn children_mut(&mut self, id: SystemId) -> Result<&mut World, SystemError> {
+        Ok(&mut self.node_mut(id)?.children)
+    }
+
+    /// Iterates the ids of every direct subsystem of `id`.
+    pub fn subsystems(
+        &self,
+        id: SystemId,
+    ) -> Result<impl Iterator<Item = SystemId> + '_, SystemError> {
+        Ok(self.node(id)?.subsystems.iter().copied())
+    }
+
+    /// Iterates the ids of the direct subsystems of `id` which are of type `S`.
+    pub fn subsystems_of<S: System>(
+        &self,
+        id: SystemId,
+    ) -> Result<impl Iterator<Item = SystemId> + '_, SystemError> {
+        let systems = &self.systems;
+
+        Ok(self
+            .node(id)?
+            .subsystems
+            .iter()
+            .copied()
+            .filter(move |child| systems[&child.index].type_id == TypeId::of::<S>()))
+    }
+
+    pub fn system<S: System>(&self, id: SystemId) -> Result<Ref<'_, S>, SystemError> {
+        let node = self.node(id)?;
+        let entity = node.entity.ok_or(SystemError::RootSystem)?;
+
+        self.node(node.parent)?
+            .children
+            .get::<S>(entity)
+            .map_err(|_| SystemError::TypeMismatch)
     }
 
-    pub fn children_mut(&self, id: SystemId) -> Result<&mut World, SystemError> {
-        Ok(&mut self
-            .systems
-            .get_mut(&id)
-            .ok_or(SystemError::InvalidSystemId)?
-            .children)
+    pub fn system_mut<S: System>(&mut self, id: SystemId) -> Result<&mut S, SystemError> {
+        let node = self.node(id)?;
+        let entity = node.entity.ok_or(SystemError::RootSystem)?;
+        let parent = node.parent;
+
+        self.node_mut(parent)?
+            .children
+            .query_one_mut::<&mut S>(entity)
+            .map_err(|_| SystemError::TypeMismatch)
     }
 
     pub fn add_system<S: System>(
@@ -101,34 +154,122 @@ impl SystemManager {
         parent: SystemId,
         system: S,
     ) -> Result<SystemId, SystemError> {
-        let parent_node = self
-            .systems
-            .get_mut(&parent)
-            .ok_or(SystemError::InvalidSystemId)?;
+        self.node(parent)?;
+
+        let id = self.next_id();
 
-        let entity = parent_node.children.spawn((system,));
+        let parent_node = self.node_mut(parent)?;
+        let entity = parent_node.children.spawn((system, id));
+        parent_node.subsystems.push(id);
 
         let node = SystemNode {
+            generation: id.generation,
             parent,
             entity: Some(entity),
+            type_id: TypeId::of::<S>(),
+            subsystems: Vec::new(),
             children: World::new(),
         };
 
-        let id = self.next_id();
-
-        self.systems.insert(id, node);
+        self.systems.insert(id.index, node);
 
         Ok(id)
     }
 
+    /// Removes a system and all of its descendants, returning their ids to the free list.
+    pub fn remove_system(&mut self, id: SystemId) -> Result<(), SystemError> {
+        let node = self.node(id)?;
+        let entity = node.entity.ok_or(SystemError::RootSystem)?;
+        let parent = node.parent;
+
+        let parent_node = self.node_mut(parent)?;
+        let _ = parent_node.children.despawn(entity);
+        parent_node.subsystems.retain(|&child| child != id);
+
+        let mut stack = vec![id];
+
+        while let Some(current) = stack.pop() {
+            if let Some(node) = self.systems.remove(&current.index) {
+                stack.extend(node.subsystems);
+                self.free_list.push(current);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves a system, along with its descendants, beneath a new parent.
+    pub fn reparent(&mut self, id: SystemId, new_parent: SystemId) -> Result<(), SystemError> {
+        let node = self.node(id)?;
+        let entity = node.entity.ok_or(SystemError::RootSystem)?;
+        let old_parent = node.parent;
+
+        self.node(new_parent)?;
+
+        let mut ancestor = new_parent;
+        loop {
+            if ancestor == id {
+                return Err(SystemError::CyclicHierarchy);
+            }
+
+            let node = self.node(ancestor)?;
+            if node.entity.is_none() {
+                break;
+            }
+            ancestor = node.parent;
+        }
+
+        if old_parent == new_parent {
+            return Ok(());
+        }
+
+        let mut old_node = self.systems.remove(&old_parent.index).unwrap();
+        old_node.subsystems.retain(|&child| child != id);
+
+        let new_node = self.systems.get_mut(&new_parent.index).unwrap();
+        let new_entity = {
+            let taken = old_node.children.take(entity).unwrap();
+            new_node.children.spawn(taken)
+        };
+        new_node.subsystems.push(id);
+
+        self.systems.insert(old_parent.index, old_node);
+
+        let node = self.systems.get_mut(&id.index).unwrap();
+        node.parent = new_parent;
+        node.entity = Some(new_entity);
+
+        Ok(())
+    }
+
+    fn node(&self, id: SystemId) -> Result<&SystemNode, SystemError> {
+        self.systems
+            .get(&id.index)
+            .filter(|node| node.generation == id.generation)
+            .ok_or(SystemError::InvalidSystemId)
+    }
+
+    fn node_mut(&mut self, id: SystemId) -> Result<&mut SystemNode, SystemError> {
+        self.systems
+            .get_mut(&id.index)
+            .filter(|node| node.generation == id.generation)
+            .ok_or(SystemError::InvalidSystemId)
+    }
+
     fn next_id(&mut self) -> SystemId {
         if let Some(last) = self.free_list.pop() {
-            return last;
+            return SystemId {
+                index: last.index,
+                generation: last.generation.wrapping_add(1),
+            };
         }
 
-        let id = self.next_id;
-        self.next_id.0 += 1;
-        id
+        let index = self.next_index;
+        self.next_index += 1;
+        SystemId {
+            index,
+            generation: 0,
+        }
     }
 }
 
@@ -141,15 +282,21 @@ pub struct SystemTree<R: Root + System> {
 
 impl<R: Root + System> SystemTree<R> {
     pub fn new(root: R) -> Self {
-        let root_id = SystemId(0);
+        let root_id = SystemId {
+            index: 0,
+            generation: 0,
+        };
 
         let mut systems = HashMap::new();
 
         systems.insert(
-            root_id,
+            root_id.index,
             SystemNode {
+                generation: root_id.generation,
                 parent: root_id,
                 entity: None,
+                type_id: TypeId::of::<R>(),
+                subsystems: Vec::new(),
                 children: World::new(),
             },
         );
@@ -161,7 +308,7 @@ impl<R: Root + System> SystemTree<R> {
                 systems,
                 config: TypeIdMap::default(),
                 free_list: Vec::new(),
-                next_id: SystemId(1),
+                next_index: 1,
             },
             context: TypeIdMap::default(),
         }
@@ -179,14 +326,16 @@ impl<R: Root + System> SystemTree<R> {
         self.root_id
     }
 
+    pub fn manager(&self) -> &SystemManager {
+        &self.manager
+    }
+
     pub fn attach_config<C: Config>(&mut self, config: C) {
-        self.manager
-            .config
-            .insert(TypeId::of::<C>(), Box::new(config));
+        self.manager.attach_config(config);
     }
 
     pub fn detach_config<C: Config>(&mut self) {
-        self.manager.config.remove(&TypeId::of::<C>());
+        self.manager.detach_config::<C>();
     }
 
     pub fn config<C: Config>(&self) -> &C {
@@ -197,13 +346,15 @@ impl<R: Root + System> SystemTree<R> {
         self.manager.config_mut()
     }
 
-    pub fn attach_context<C: Context<R>>(&mut self, context: C) {
+    pub fn attach_context<C: Context<R>>(&mut self, mut context: C) {
         context.on_attach(&self.root);
-        self.context.insert(TypeId::of::<C>(), Box::new(context));
+        if let Some(mut old) = self.context.insert(TypeId::of::<C>(), Box::new(context)) {
+            old.on_detach();
+        }
     }
 
     pub fn detach_context<C: Context<R>>(&mut self) {
-        if let Some(context) = self.context.remove(&TypeId::of::<C>()) {
+        if let Some(mut context) = self.context.remove(&TypeId::of::<C>()) {
             context.on_detach();
         }
     }
@@ -222,12 +373,113 @@ impl<R: Root + System> SystemTree<R> {
         self.manager.entity(id)
     }
 
+    pub fn parent(&self, id: SystemId) -> Result<SystemId, SystemError> {
+        self.manager.parent(id)
+    }
+
+    pub fn children(&self, id: SystemId) -> Result<&World, SystemError> {
+        self.manager.children(id)
+    }
+
+    pub fn subsystems(
+        &self,
+        id: SystemId,
+    ) -> Result<impl Iterator<Item = SystemId> + '_, SystemError> {
+        self.manager.subsystems(id)
+    }
+
+    pub fn subsystems_of<S: System>(
+        &self,
+        id: SystemId,
+    ) -> Result<impl Iterator<Item = SystemId> + '_, SystemError> {
+        self.manager.subsystems_of::<S>(id)
+    }
+
+    pub fn system<S: System>(&self, id: SystemId) -> Result<Ref<'_, S>, SystemError> {
+        self.manager.system(id)
+    }
+
     pub fn add_system<S: System>(
         &mut self,
         parent: SystemId,
         system: S,
     ) -> Result<SystemId, SystemError> {
-        self.manager.add_system(parent, system)
+        self.manager.node(parent)?;
+
+        self.edit_begin(parent);
+        let result = self.manager.add_system(parent, system);
+        self.edit_end(parent);
+
+        result
+    }
+
+    pub fn remove_system(&mut self, id: SystemId) -> Result<(), SystemError> {
+        let parent = self.manager.parent(id)?;
+
+        self.edit_begin(parent);
+        let result = self.manager.remove_system(id);
+        self.edit_end(parent);
+
+        result
+    }
+
+    pub fn reparent(&mut self, id: SystemId, new_parent: SystemId) -> Result<(), SystemError> {
+        let old_parent = self.manager.parent(id)?;
+        self.manager.node(new_parent)?;
+
+        self.edit_begin(old_parent);
+        self.edit_begin(new_parent);
+        let result = self.manager.reparent(id, new_parent);
+        self.edit_end(new_parent);
+        self.edit_end(old_parent);
+
+        result
+    }
+
+    /// Modifies a system and its children, notifying every attached context of the edit.
+    pub fn edit<S: System, T>(
+        &mut self,
+        id: SystemId,
+        f: impl FnOnce(&mut S, &mut World) -> T,
+    ) -> Result<T, SystemError> {
+        let parent = self.manager.parent(id)?;
+        let entity = self.manager.entity(id)?;
+
+        if self.manager.node(id)?.type_id != TypeId::of::<S>() {
+            return Err(SystemError::TypeMismatch);
+        }
+
+        self.edit_begin(id);
+
+        // Both the system and its children are owned by the manager, but live in different worlds.
+        let mut children = std::mem::take(&mut self.manager.node_mut(id)?.children);
+        let result = {
+            let system = self
+                .manager
+                .node_mut(parent)?
+                .children
+                .query_one_mut::<&mut S>(entity)
+                .map_err(|_| SystemError::TypeMismatch);
+
+            system.map(|system| f(system, &mut children))
+        };
+        self.manager.node_mut(id)?.children = children;
+
+        self.edit_end(id);
+
+        result
+    }
+
+    fn edit_begin(&mut self, system: SystemId) {
+        for context in self.context.values_mut() {
+            context.on_edit_begin(&self.manager, system);
+        }
+    }
+
+    fn edit_end(&mut self, system: SystemId) {
+        for context in self.context.values_mut() {
+            context.on_edit_end(&self.manager, system);
+        }
     }
 }
 
@@ -279,7 +531,153 @@ impl Hasher for TypeIdHasher {
 type TypeIdMap<V> = HashMap<TypeId, V, BuildHasherDefault<TypeIdHasher>>;
 
 struct SystemNode {
+    generation: u32,
     parent: SystemId,
     entity: Option<Entity>,
+    type_id: TypeId,
+    subsystems: Vec<SystemId>,
     children: World,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Galaxy;
+
+    impl Root for Galaxy {}
+
+    struct Cluster {
+        stars: usize,
+    }
+
+    struct Field;
+
+    macro_rules! impl_system {
+        ($ty:ty) => {
+            impl System for $ty {
+                fn on_create(&mut self, _manager: &mut SystemManager, _time: f64) {}
+                fn on_update(&mut self, _manager: &mut SystemManager, _time: f64, _delta: f64) {}
+                fn on_destroy(&mut self, _manager: &mut SystemManager, _time: f64) {}
+
+                fn as_any(&self) -> &dyn Any {
+                    self
+                }
+
+                fn as_any_mut(&mut self) -> &mut dyn Any {
+                    self
+                }
+            }
+        };
+    }
+
+    impl_system!(Galaxy);
+    impl_system!(Cluster);
+    impl_system!(Field);
+
+    #[derive(Default)]
+    struct EditLog {
+        edits: Vec<(bool, SystemId)>,
+    }
+
+    impl Context<Galaxy> for EditLog {
+        fn on_attach(&mut self, _root: &Galaxy) {}
+        fn on_detach(&mut self) {}
+
+        fn on_edit_begin(&mut self, _manager: &SystemManager, system: SystemId) {
+            self.edits.push((true, system));
+        }
+
+        fn on_edit_end(&mut self, _manager: &SystemManager, system: SystemId) {
+            self.edits.push((false, system));
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[test]
+    fn hierarchy() {
+        let mut tree = SystemTree::new(Galaxy);
+        let root = tree.root_id();
+
+        let a = tree.add_system(root, Cluster { stars: 1 }).unwrap();
+        let b = tree.add_system(root, Cluster { stars: 2 }).unwrap();
+        let field = tree.add_system(root, Field).unwrap();
+        let nested = tree.add_system(a, Cluster { stars: 3 }).unwrap();
+
+        assert_eq!(tree.subsystems(root).unwrap().count(), 3);
+        assert_eq!(
+            tree.subsystems_of::<Cluster>(root)
+                .unwrap()
+                .collect::<Vec<_>>(),
+            vec![a, b]
+        );
+        assert_eq!(tree.system::<Cluster>(nested).unwrap().stars, 3);
+        assert!(tree.system::<Field>(a).is_err());
+
+        tree.reparent(a, b).unwrap();
+        assert_eq!(tree.parent(a).unwrap(), b);
+        assert_eq!(tree.system::<Cluster>(a).unwrap().stars, 1);
+        assert_eq!(tree.parent(nested).unwrap(), a);
+        assert!(matches!(
+            tree.reparent(b, nested),
+            Err(SystemError::CyclicHierarchy)
+        ));
+
+        tree.remove_system(b).unwrap();
+        assert!(tree.system::<Cluster>(nested).is_err());
+        assert_eq!(
+            tree.subsystems(root).unwrap().collect::<Vec<_>>(),
+            vec![field]
+        );
+        assert_eq!(tree.children(root).unwrap().len(), 1);
+
+        // Freed slots are reused, but ids of removed systems stay invalid
+        let reused = tree.add_system(root, Field).unwrap();
+        assert!([a, b, nested].iter().all(|&removed| reused != removed));
+        assert!([a, b, nested]
+            .iter()
+            .any(|removed| reused.index == removed.index));
+        assert!(tree.system::<Field>(reused).is_ok());
+        for removed in [a, b, nested] {
+            assert!(!tree.manager().contains(removed));
+            assert!(matches!(
+                tree.system::<Field>(removed),
+                Err(SystemError::InvalidSystemId)
+            ));
+        }
+    }
+
+    #[test]
+    fn contexts_observe_edits() {
+        let mut tree = SystemTree::new(Galaxy);
+        tree.attach_context(EditLog::default());
+
+        let root = tree.root_id();
+        let cluster = tree.add_system(root, Cluster { stars: 0 }).unwrap();
+
+        tree.edit::<Cluster, _>(cluster, |cluster, children| {
+            cluster.stars += 1;
+            children.spawn((0usize,));
+        })
+        .unwrap();
+
+        assert_eq!(tree.system::<Cluster>(cluster).unwrap().stars, 1);
+        assert_eq!(tree.children(cluster).unwrap().len(), 1);
+        assert_eq!(
+            tree.context::<EditLog>().edits,
+            vec![
+                (true, root),
+                (false, root),
+                (true, cluster),
+                (false, cluster)
+            ]
+        );
+    }
+}
@@ -10,7 +10,7 @@ use grav_descriptor::{GravDescriptor, NBodyStarDescriptor};
 use solve_descriptor::SolveDescriptor;
 use system_manager::SystemManager;
 use system_tree::{SystemTreeGD, SystemTreeRoot};
-use units_descriptor::UnitsDescriptor;
+use units_descriptor::{UnitsDescriptor, UnitsError};
 
 // Function that registers all exposed classes to Godot
 fn init(handle: InitHandle) {
@@ -1,28 +1,95 @@
-use super::{GravDescriptor, SystemTreeGD, SystemTreeRoot};
-use super::{SolveDescriptor, UnitsDescriptor};
-use crate::base::SystemTree;
+use super::{GravDescriptor, NBodyStarDescriptor, SystemTreeGD, SystemTreeRoot};
+use super::{SolveDescriptor, UnitsDescriptor, UnitsError};
+use crate::base::{ContinuousRecord, Subsystem, SystemNode, SystemTree};
+use crate::gravity::nbody::{NBody, NBodySystem, Position, Star, Velocity};
 use crate::gravity::GravitationalSystem;
 use gdnative::api::Tree;
 use gdnative::prelude::*;
-use std::fs::File;
-use std::io::{Read, Seek, SeekFrom, Write};
-use std::path::PathBuf;
+use glam::DVec3;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum LoadError {
-    #[error("Failed to open file")]
-    FileSystemError,
-    #[error("Failed to deserialize contents of file")]
-    DeserializationError,
+    #[error("Failed to read {}", path.display())]
+    FileSystemError {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Failed to deserialize contents of {}", path.display())]
+    DeserializationError {
+        path: PathBuf,
+        #[source]
+        source: bincode::Error,
+    },
+}
+
+impl LoadError {
+    /// Short identifier of the kind of error, for scripts to branch on.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::FileSystemError { .. } => "file_system",
+            Self::DeserializationError { .. } => "deserialization",
+        }
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum SaveError {
-    #[error("FileSystem Error")]
-    FileSystemError,
+    #[error("Failed to write {}", path.display())]
+    FileSystemError {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
     #[error("Failed to serialize tree")]
-    SerializationError,
+    SerializationError(#[source] bincode::Error),
+    #[error("Failed to back up {}", path.display())]
+    BackupError {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Failed to access system tree")]
+    AccessError,
+    #[error("Cannot save a system tree while it is solving")]
+    Solving,
+}
+
+impl SaveError {
+    /// Short identifier of the kind of error, for scripts to branch on.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::FileSystemError { .. } => "file_system",
+            Self::SerializationError(_) => "serialization",
+            Self::BackupError { .. } => "backup",
+            Self::AccessError => "access",
+            Self::Solving => "solving",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CreateError {
+    #[error("Failed to access descriptor")]
+    AccessError,
+    #[error("System name must not be empty")]
+    EmptyName,
+    #[error("Invalid units")]
+    InvalidUnits(#[from] UnitsError),
+    #[error("Body {0} is not a star descriptor")]
+    InvalidBody(usize),
+    #[error("Body {0} must have a finite, non-negative mass")]
+    InvalidMass(usize),
+    #[error("Body {0} must have a finite, non-negative temperature")]
+    InvalidTemperature(usize),
+    #[error("Body {0} must have a finite position and velocity")]
+    InvalidState(usize),
 }
 
 #[derive(NativeClass)]
@@ -36,92 +103,244 @@ impl SystemManager {
         Self {}
     }
 
-    // #[export]
-    // fn create_grav(
-    //     &self,
-    //     _owner: &Reference,
-    //     desc: Instance<GravDescriptor, Shared>,
-    // ) -> Instance<SystemTreeGD, Unique> {
-    //     let desc = unsafe { desc.assume_safe() };
-
-    //     let desc =
-    //         match desc.map(|desc: &GravDescriptor, _base: TRef<Reference, Shared>| desc.clone()) {
-    //             Err(error) => {
-    //                 godot_error!("Failed to access descriptor with error {:?}", error);
-    //                 GravDescriptor::default()
-    //             }
-    //             Ok(desc) => desc,
-    //         };
-
-    //     SystemTreeGD::new(
-    //         desc.name,
-    //         SystemTreeRoot::Grav(SystemTree::new(GravitationalSystem)),
-    //     )
-    //     .emplace()
-    // }
+    /// Creates a gravitational system from descriptors, returning a dictionary with `ok`, a
+    /// `message` describing any error and the created `tree`.
+    #[export]
+    fn create_grav(
+        &self,
+        _owner: &Reference,
+        desc: Instance<GravDescriptor, Shared>,
+        units: Instance<UnitsDescriptor, Shared>,
+        bodies: VariantArray<Shared>,
+    ) -> Dictionary<Unique> {
+        let status = Dictionary::new();
 
+        match create_grav(desc, units, bodies) {
+            Ok(tree) => {
+                status.insert("ok", true);
+                status.insert("message", format!("Created {}", tree.name));
+                status.insert("tree", tree.emplace().into_shared());
+            }
+            Err(error) => {
+                godot_error!(
+                    "Failed to create gravitational system with error {:?}",
+                    error
+                );
+                status.insert("ok", false);
+                status.insert("message", error.to_string());
+                status.insert("tree", Variant::new());
+            }
+        }
+
+        status
+    }
+
+    /// Loads a tree from `path`, returning a dictionary with `ok`, the `kind` of any error, a
+    /// `message` describing the outcome and the loaded `tree`.
     #[export]
-    fn load(&self, _owner: &Reference, path: GodotString) -> Instance<SystemTreeGD, Unique> {
+    fn load(&self, _owner: &Reference, path: GodotString) -> Dictionary<Unique> {
         let path = PathBuf::from(path.to_string());
+        let status = Dictionary::new();
 
-        match load_hierarchy(path.clone()) {
-            Ok(tree) => tree.emplace(),
+        match load_hierarchy(&path) {
+            Ok(tree) => {
+                status.insert("ok", true);
+                status.insert("kind", "");
+                status.insert("message", format!("Loaded {}", path.display()));
+                status.insert("tree", tree.emplace().into_shared());
+            }
             Err(error) => {
                 godot_error!("Failed to load hierarchy with error {:?}", error);
-                SystemTreeGD::empty().emplace()
+                status.insert("ok", false);
+                status.insert("kind", error.kind());
+                status.insert("message", describe(&error));
+                status.insert("tree", Variant::new());
             }
         }
+
+        status
     }
 
+    /// Saves a tree to `path`, returning a dictionary with `ok`, the `kind` of any error and a
+    /// `message` describing the outcome. The file is replaced atomically, and with `backup` the
+    /// previous file is kept next to it with a `.bak` suffix.
     #[export]
     fn save(
         &self,
         _owner: &Reference,
-        _path: GodotString,
-        _system_tree: Instance<SystemTreeGD, Shared>,
-    ) {
-        // let path = PathBuf::from(path.to_string());
-        // let system_tree = unsafe { system_tree.assume_safe() };
-
-        // match system_tree.map(|hierarchy: &SystemTreeGD, _base: TRef<Reference, Shared>| {
-        //     match save_hierarchy(path, hierarchy) {
-        //         Err(error) => {
-        //             godot_error!("Failed to save hierarchy with error {:?}", error);
-        //         }
-        //         _ => {}
-        //     };
-        // }) {
-        //     Err(error) => {
-        //         godot_error!("Failed to access hierarchy with error {:?}", error);
-        //     }
-        //     _ => {}
-        // }
+        path: GodotString,
+        system_tree: Instance<SystemTreeGD, Shared>,
+        #[opt] backup: bool,
+    ) -> Dictionary<Unique> {
+        let path = PathBuf::from(path.to_string());
+        let system_tree = unsafe { system_tree.assume_safe() };
+
+        let result = system_tree
+            .map(|hierarchy: &SystemTreeGD, _base: TRef<Reference, Shared>| {
+                save_hierarchy(&path, hierarchy, backup)
+            })
+            .map_err(|_error| SaveError::AccessError)
+            .and_then(|result| result);
+
+        let status = Dictionary::new();
+
+        match result {
+            Ok(()) => {
+                status.insert("ok", true);
+                status.insert("kind", "");
+                status.insert("message", format!("Saved to {}", path.display()));
+            }
+            Err(error) => {
+                godot_error!("Failed to save hierarchy with error {:?}", error);
+                status.insert("ok", false);
+                status.insert("kind", error.kind());
+                status.insert("message", describe(&error));
+            }
+        }
+
+        status
     }
 }
 
-fn load_hierarchy(path: PathBuf) -> Result<SystemTreeGD, LoadError> {
-    let mut file = File::open(&path).map_err(|_error| LoadError::FileSystemError)?;
+fn create_grav(
+    desc: Instance<GravDescriptor, Shared>,
+    units: Instance<UnitsDescriptor, Shared>,
+    bodies: VariantArray<Shared>,
+) -> Result<SystemTreeGD, CreateError> {
+    let desc = unsafe { desc.assume_safe() }
+        .map(|desc: &GravDescriptor, _base: TRef<Reference, Shared>| desc.clone())
+        .map_err(|_error| CreateError::AccessError)?;
 
-    let len = file
-        .seek(SeekFrom::End(0))
-        .map_err(|_error| LoadError::FileSystemError)?;
-    file.seek(SeekFrom::Start(0))
-        .map_err(|_error| LoadError::FileSystemError)?;
+    let units = unsafe { units.assume_safe() }
+        .map(|units: &UnitsDescriptor, _base: TRef<Reference, Shared>| units.to_units())
+        .map_err(|_error| CreateError::AccessError)??;
 
-    let mut contents = vec![0u8; len as usize];
+    if desc.name.trim().is_empty() {
+        return Err(CreateError::EmptyName);
+    }
 
-    file.read(&mut contents)
-        .map_err(|_error| LoadError::FileSystemError)?;
+    let mut nbodies = SystemNode::new(NBodySystem);
 
-    Ok(bincode::deserialize(&contents).map_err(|_e| LoadError::DeserializationError)?)
+    for (index, body) in bodies.iter().enumerate() {
+        let body = Instance::<NBodyStarDescriptor, Shared>::from_variant(&body)
+            .map_err(|_error| CreateError::InvalidBody(index))?;
+
+        let body = unsafe { body.assume_safe() }
+            .map(|body: &NBodyStarDescriptor, _base: TRef<Reference, Shared>| body.clone())
+            .map_err(|_error| CreateError::AccessError)?;
+
+        if !body.mass.is_finite() || body.mass < 0.0 {
+            return Err(CreateError::InvalidMass(index));
+        }
+
+        if !body.temp.is_finite() || body.temp < 0.0 {
+            return Err(CreateError::InvalidTemperature(index));
+        }
+
+        let pos = DVec3::new(body.pos.x as f64, body.pos.y as f64, body.pos.z as f64);
+        let vel = DVec3::new(body.vel.x as f64, body.vel.y as f64, body.vel.z as f64);
+
+        if !pos.is_finite() || !vel.is_finite() {
+            return Err(CreateError::InvalidState(index));
+        }
+
+        nbodies.children_mut().spawn((
+            NBody {
+                index,
+                pos,
+                vel,
+                mass: body.mass,
+            },
+            ContinuousRecord::<Position>::new(),
+            ContinuousRecord::<Velocity>::new(),
+            Star {
+                temperature: body.temp,
+            },
+        ));
+    }
+
+    let mut tree = SystemTree::new(GravitationalSystem);
+    tree.config_mut().insert(units);
+    tree.root_mut()
+        .children_mut()
+        .spawn((Subsystem::new(nbodies),));
+
+    Ok(SystemTreeGD::new(desc.name, SystemTreeRoot::Grav(tree)))
+}
+
+/// Describes an error followed by the chain of errors which caused it.
+fn describe(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+
+    message
+}
+
+fn load_hierarchy(path: &Path) -> Result<SystemTreeGD, LoadError> {
+    let contents = fs::read(path).map_err(|source| LoadError::FileSystemError {
+        path: path.to_owned(),
+        source,
+    })?;
+
+    bincode::deserialize(&contents).map_err(|source| LoadError::DeserializationError {
+        path: path.to_owned(),
+        source,
+    })
 }
 
-fn save_hierarchy(path: PathBuf, tree: &SystemTreeGD) -> Result<(), SaveError> {
-    let contents = bincode::serialize(tree).map_err(|_e| SaveError::SerializationError)?;
+fn save_hierarchy(path: &Path, tree: &SystemTreeGD, backup: bool) -> Result<(), SaveError> {
+    if tree.solve_running() {
+        return Err(SaveError::Solving);
+    }
+
+    let contents = bincode::serialize(tree).map_err(SaveError::SerializationError)?;
+
+    // Write everything to a temporary file first, so a failed save never leaves a truncated file
+    // in place of the previous one.
+    let temp = with_suffix(path, ".tmp");
+
+    let write_error = |source| SaveError::FileSystemError {
+        path: path.to_owned(),
+        source,
+    };
 
-    let mut file = File::create(path).map_err(|_e| SaveError::FileSystemError)?;
-    file.write_all(&contents)
-        .map_err(|_e| SaveError::FileSystemError)?;
+    let result = write_synced(&temp, &contents)
+        .map_err(write_error)
+        .and_then(|()| {
+            if backup && path.exists() {
+                fs::copy(path, with_suffix(path, ".bak")).map_err(|source| {
+                    SaveError::BackupError {
+                        path: path.to_owned(),
+                        source,
+                    }
+                })?;
+            }
+
+            fs::rename(&temp, path).map_err(write_error)
+        });
+
+    // The temporary file is only left behind if it was renamed into place
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+
+    result
+}
+
+fn write_synced(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
 
-    Ok(())
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
 }
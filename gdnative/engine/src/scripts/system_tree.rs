@@ -1,10 +1,22 @@
-use crate::base::ContinuousRecord;
-use crate::base::{SystemNode, SystemTree};
-use crate::gravity::nbody::Position;
-use crate::gravity::nbody::{NBody, NBodySystem};
-use crate::gravity::GravitationalSystem;
+use super::{SolveDescriptor, UnitsDescriptor};
+use crate::base::{
+    Command, ContinuousRecord, EditError, Event, Inspected, RayTolerance, RegisteredSystem,
+    SetConfig, SolveHandle, SolveOutcome, SolveProgress, SpatialIndex, Subsystem, SystemTree,
+    Value,
+};
+use crate::global::Units;
+use crate::gravity::edit::{AddBody, BodyProperty, RemoveBody, SetBody};
+use crate::gravity::event::GravitationalEvent;
+use crate::gravity::nbody::{NBody, NBodySystem, Position, ViewState};
+use crate::gravity::trail::{self, TrailPoint};
+use crate::gravity::{self, BodyId, GravitationalSystem};
+use gdnative::core_types::typed_array::Element;
 use gdnative::prelude::*;
+use glam::DVec3;
 use serde::{Deserialize, Serialize};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
 
 #[derive(Serialize, Deserialize)]
 pub enum SystemTreeRoot {
@@ -15,23 +27,98 @@ pub enum SystemTreeRoot {
 #[derive(NativeClass)]
 #[inherit(Reference)]
 #[no_constructor]
+#[register_with(Self::register_signals)]
 #[derive(Serialize, Deserialize)]
 pub struct SystemTreeGD {
     pub name: String,
     pub root: SystemTreeRoot,
+    /// A solve running on a worker thread. The root is moved to the worker for the duration.
+    #[serde(skip)]
+    job: Option<SolveJob>,
+}
+
+/// Messages sent from the solve worker back to the owning `SystemTreeGD`.
+enum SolveMessage {
+    Finished(SystemTreeRoot, SolveOutcome),
+    Failed(String),
+}
+
+struct SolveJob {
+    handle: SolveHandle,
+    receiver: Receiver<SolveMessage>,
+    last_step: usize,
 }
 
 impl SystemTreeGD {
     pub fn new(name: String, root: SystemTreeRoot) -> Self {
-        Self { name, root }
+        Self {
+            name,
+            root,
+            job: None,
+        }
     }
 
     pub fn empty() -> Self {
         Self {
             name: String::from("Empty"),
             root: SystemTreeRoot::None,
+            job: None,
         }
     }
+
+    fn register_signals(builder: &ClassBuilder<Self>) {
+        builder.add_signal(Signal {
+            name: "solve_progressed",
+            args: &[
+                SignalArgument {
+                    name: "time",
+                    default: Variant::from_f64(0.0),
+                    export_info: ExportInfo::new(VariantType::F64),
+                    usage: PropertyUsage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "fraction",
+                    default: Variant::from_f64(0.0),
+                    export_info: ExportInfo::new(VariantType::F64),
+                    usage: PropertyUsage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "remaining_seconds",
+                    default: Variant::from_f64(-1.0),
+                    export_info: ExportInfo::new(VariantType::F64),
+                    usage: PropertyUsage::DEFAULT,
+                },
+            ],
+        });
+
+        builder.add_signal(Signal {
+            name: "solve_finished",
+            args: &[
+                SignalArgument {
+                    name: "time",
+                    default: Variant::from_f64(0.0),
+                    export_info: ExportInfo::new(VariantType::F64),
+                    usage: PropertyUsage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "cancelled",
+                    default: Variant::from_bool(false),
+                    export_info: ExportInfo::new(VariantType::Bool),
+                    usage: PropertyUsage::DEFAULT,
+                },
+            ],
+        });
+
+        builder.add_signal(Signal {
+            name: "solve_failed",
+            args: &[SignalArgument {
+                name: "message",
+                default: Variant::from_str(""),
+                export_info: ExportInfo::new(VariantType::GodotString),
+                usage: PropertyUsage::DEFAULT,
+            }],
+        });
+    }
 }
 
 #[methods]
@@ -44,41 +131,857 @@ impl SystemTreeGD {
         false
     }
 
+    /// Returns the key of the root system, or an empty string for an empty tree.
+    #[export]
+    fn root_type(&self, _owner: &Reference) -> GodotString {
+        match self.root {
+            SystemTreeRoot::Grav(_) => GodotString::from_str(GravitationalSystem::KEY),
+            SystemTreeRoot::None => GodotString::new(),
+        }
+    }
+
+    /// Returns an outline of the subsystems of the root. Each item is a dictionary with an `id`,
+    /// its `type`, a `child_count`, its `components` as a dictionary of field dictionaries and
+    /// its `children`. Subsystems are identified by entity and bodies by their index.
+    #[export]
+    fn outline(&self, _owner: &Reference) -> VariantArray<Unique> {
+        match self.root {
+            SystemTreeRoot::Grav(ref tree) => to_outline(&tree.root().inspect()),
+            SystemTreeRoot::None => VariantArray::new(),
+        }
+    }
+
     #[export]
     fn name(&self, _onwer: &Reference) -> GodotString {
         GodotString::from_str(&self.name)
     }
 
+    /// Starts solving the tree on a worker thread. Views and edits are unavailable until
+    /// `solve_finished` or `solve_failed` is emitted from `poll`.
     #[export]
-    fn positions(&mut self, _owner: &Reference, time: f64) -> VariantArray<Unique> {
-        let mut vector = Vec::new();
+    fn solve(&mut self, owner: &Reference, desc: Instance<SolveDescriptor, Shared>) -> bool {
+        let desc = unsafe { desc.assume_safe() };
 
-        if let SystemTreeRoot::Grav(ref mut tree) = self.root {
-            if let Some((_e, nbody)) = tree
-                .root_mut()
-                .children_mut()
-                .query_mut::<&mut SystemNode<NBodySystem>>()
-                .into_iter()
-                .next()
-            {
-                for (_e, (body, record)) in nbody
-                    .children_mut()
-                    .query_mut::<(&NBody, &ContinuousRecord<Position>)>()
-                {
-                    let pos = record.load(time);
-                    vector.push((body.index, pos.pos));
+        let desc =
+            match desc.map(|desc: &SolveDescriptor, _base: TRef<Reference, Shared>| desc.clone()) {
+                Ok(desc) => desc,
+                Err(error) => {
+                    return self.fail_solve(
+                        owner,
+                        format!("Failed to access solve descriptor with error {:?}", error),
+                    );
+                }
+            };
+
+        if self.job.is_some() {
+            return self.fail_solve(owner, String::from("A solve is already running"));
+        }
+
+        if desc.iterations < 0 || !desc.start_time.is_finite() || !desc.end_time.is_finite() {
+            return self.fail_solve(owner, String::from("Invalid solve descriptor"));
+        }
+
+        if let SystemTreeRoot::Grav(ref tree) = self.root {
+            if let Err(error) = tree.check_solve(desc.start_time, desc.end_time) {
+                return self.fail_solve(owner, error.to_string());
+            }
+        }
+
+        let mut root = match std::mem::replace(&mut self.root, SystemTreeRoot::None) {
+            SystemTreeRoot::None => {
+                return self.fail_solve(owner, String::from("Tree is empty"));
+            }
+            root => root,
+        };
+
+        let handle = SolveHandle::new();
+        let worker_handle = handle.clone();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                let outcome = match root {
+                    SystemTreeRoot::Grav(ref mut tree) => tree.solve_observed(
+                        desc.start_time,
+                        desc.end_time,
+                        desc.iterations as usize,
+                        &worker_handle,
+                        &mut |_: &SolveProgress| {},
+                    ),
+                    SystemTreeRoot::None => SolveOutcome::Completed,
+                };
+                (root, outcome)
+            }));
+
+            let message = match result {
+                Ok((root, outcome)) => SolveMessage::Finished(root, outcome),
+                Err(_) => SolveMessage::Failed(String::from("Solver panicked")),
+            };
+
+            let _ = sender.send(message);
+        });
+
+        self.job = Some(SolveJob {
+            handle,
+            receiver,
+            last_step: 0,
+        });
+
+        true
+    }
+
+    /// Emits signals for the progress of a running solve. Should be called every frame.
+    #[export]
+    fn poll(&mut self, owner: &Reference) {
+        let job = match self.job.as_mut() {
+            Some(job) => job,
+            None => return,
+        };
+
+        if let Some(progress) = job.handle.progress() {
+            if progress.step != job.last_step {
+                job.last_step = progress.step;
+
+                let remaining = progress
+                    .remaining
+                    .map(|remaining| remaining.as_secs_f64())
+                    .unwrap_or(-1.0);
+
+                owner.emit_signal(
+                    "solve_progressed",
+                    &[
+                        progress.time.to_variant(),
+                        progress.fraction().to_variant(),
+                        remaining.to_variant(),
+                    ],
+                );
+            }
+        }
+
+        match job.receiver.try_recv() {
+            Ok(SolveMessage::Finished(root, outcome)) => {
+                self.root = root;
+                self.job = None;
+
+                let (time, cancelled) = match outcome {
+                    SolveOutcome::Completed => (self.solved_end().unwrap_or(0.0), false),
+                    SolveOutcome::Cancelled(time) => (time, true),
+                };
+
+                owner.emit_signal(
+                    "solve_finished",
+                    &[time.to_variant(), cancelled.to_variant()],
+                );
+            }
+            Ok(SolveMessage::Failed(message)) => {
+                self.job = None;
+                owner.emit_signal("solve_failed", &[message.to_variant()]);
+            }
+            Err(TryRecvError::Disconnected) => {
+                self.job = None;
+                owner.emit_signal(
+                    "solve_failed",
+                    &[String::from("Solver stopped unexpectedly").to_variant()],
+                );
+            }
+            Err(TryRecvError::Empty) => {}
+        }
+    }
+
+    /// Asks a running solve to stop after its current step. The tree keeps everything solved so far.
+    #[export]
+    fn cancel_solve(&self, _owner: &Reference) {
+        if let Some(job) = self.job.as_ref() {
+            job.handle.cancel();
+        }
+    }
+
+    #[export]
+    fn is_solving(&self, _owner: &Reference) -> bool {
+        self.job.is_some()
+    }
+
+    #[export]
+    fn add_body(
+        &mut self,
+        _owner: &Reference,
+        index: i64,
+        pos: Vector3,
+        vel: Vector3,
+        mass: f64,
+    ) -> bool {
+        let index = match usize::try_from(index) {
+            Ok(index) => index,
+            Err(_) => return false,
+        };
+
+        self.edit(AddBody::new(NBody {
+            index,
+            pos: DVec3::new(pos.x as f64, pos.y as f64, pos.z as f64),
+            vel: DVec3::new(vel.x as f64, vel.y as f64, vel.z as f64),
+            mass,
+        }))
+    }
+
+    /// Removes a body. Like the other body edits, the body is identified by its subsystem and
+    /// index as given by `frame` or `view`.
+    #[export]
+    fn remove_body(&mut self, _owner: &Reference, subsystem: i64, index: i64) -> bool {
+        match self.body_id(subsystem, index) {
+            Some(id) => self.edit(RemoveBody::new(id)),
+            None => false,
+        }
+    }
+
+    #[export]
+    fn set_body_position(
+        &mut self,
+        _owner: &Reference,
+        subsystem: i64,
+        index: i64,
+        pos: Vector3,
+    ) -> bool {
+        let pos = DVec3::new(pos.x as f64, pos.y as f64, pos.z as f64);
+
+        match self.body_id(subsystem, index) {
+            Some(id) => self.edit(SetBody::new(id, BodyProperty::Position(pos))),
+            None => false,
+        }
+    }
+
+    #[export]
+    fn set_body_velocity(
+        &mut self,
+        _owner: &Reference,
+        subsystem: i64,
+        index: i64,
+        vel: Vector3,
+    ) -> bool {
+        let vel = DVec3::new(vel.x as f64, vel.y as f64, vel.z as f64);
+
+        match self.body_id(subsystem, index) {
+            Some(id) => self.edit(SetBody::new(id, BodyProperty::Velocity(vel))),
+            None => false,
+        }
+    }
+
+    #[export]
+    fn set_body_mass(&mut self, _owner: &Reference, subsystem: i64, index: i64, mass: f64) -> bool {
+        match self.body_id(subsystem, index) {
+            Some(id) => self.edit(SetBody::new(id, BodyProperty::Mass(mass))),
+            None => false,
+        }
+    }
+
+    /// Sets the initial position and velocity of a body as a single edit.
+    #[export]
+    fn set_body_state(
+        &mut self,
+        _owner: &Reference,
+        subsystem: i64,
+        index: i64,
+        pos: Vector3,
+        vel: Vector3,
+    ) -> bool {
+        let id = match self.body_id(subsystem, index) {
+            Some(id) => id,
+            None => return false,
+        };
+
+        let pos = DVec3::new(pos.x as f64, pos.y as f64, pos.z as f64);
+        let vel = DVec3::new(vel.x as f64, vel.y as f64, vel.z as f64);
+
+        self.with_tree(|tree| {
+            tree.begin_transaction()?;
+
+            let result = tree
+                .edit(SetBody::new(id, BodyProperty::Position(pos)))
+                .and_then(|()| tree.edit(SetBody::new(id, BodyProperty::Velocity(vel))));
+
+            match result {
+                Ok(()) => tree.commit_transaction(),
+                Err(error) => {
+                    tree.rollback_transaction()?;
+                    Err(error)
                 }
             }
+        })
+    }
+
+    /// Names a body. Unlike the initial state, names do not make the tree stale.
+    #[export]
+    fn set_body_name(
+        &mut self,
+        _owner: &Reference,
+        subsystem: i64,
+        index: i64,
+        name: GodotString,
+    ) -> bool {
+        match self.body_id(subsystem, index) {
+            Some(id) => self.edit(SetBody::new(id, BodyProperty::Name(name.to_string()))),
+            None => false,
+        }
+    }
+
+    /// Sets the temperature of a star, making the body a star if it was not one.
+    #[export]
+    fn set_body_temperature(
+        &mut self,
+        _owner: &Reference,
+        subsystem: i64,
+        index: i64,
+        temperature: f64,
+    ) -> bool {
+        match self.body_id(subsystem, index) {
+            Some(id) => self.edit(SetBody::new(id, BodyProperty::Temperature(temperature))),
+            None => false,
+        }
+    }
+
+    /// Returns the units of the tree, which are the default units if it has none.
+    #[export]
+    fn units(&self, _owner: &Reference) -> Instance<UnitsDescriptor, Unique> {
+        let units = match self.root {
+            SystemTreeRoot::Grav(ref tree) => tree.config().get::<Units>().copied(),
+            SystemTreeRoot::None => None,
+        };
+
+        UnitsDescriptor::from_units(&units.unwrap_or_default()).emplace()
+    }
+
+    /// Changes the units of the tree. The gravitational constant and speed of light the bodies move
+    /// under are derived from the units, so this makes the tree stale.
+    #[export]
+    fn set_units(&mut self, _owner: &Reference, desc: Instance<UnitsDescriptor, Shared>) -> bool {
+        let units = unsafe { desc.assume_safe() }
+            .map(|desc: &UnitsDescriptor, _base: TRef<Reference, Shared>| desc.to_units());
+
+        match units {
+            Ok(Ok(units)) => self.edit(SetConfig::new(units)),
+            Ok(Err(error)) => {
+                godot_error!("Failed to set units with error {:?}", error);
+                false
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Groups edits until `commit_edit` so they are undone together.
+    #[export]
+    fn begin_edit(&mut self, _owner: &Reference) -> bool {
+        self.with_tree(|tree| tree.begin_transaction())
+    }
+
+    #[export]
+    fn commit_edit(&mut self, _owner: &Reference) -> bool {
+        self.with_tree(|tree| tree.commit_transaction())
+    }
+
+    #[export]
+    fn rollback_edit(&mut self, _owner: &Reference) -> bool {
+        self.with_tree(|tree| tree.rollback_transaction())
+    }
+
+    #[export]
+    fn undo(&mut self, _owner: &Reference) -> bool {
+        self.with_tree(|tree| tree.undo())
+    }
+
+    #[export]
+    fn redo(&mut self, _owner: &Reference) -> bool {
+        self.with_tree(|tree| tree.redo())
+    }
+
+    #[export]
+    fn can_undo(&self, _owner: &Reference) -> bool {
+        match self.root {
+            SystemTreeRoot::Grav(ref tree) => tree.can_undo(),
+            SystemTreeRoot::None => false,
         }
+    }
 
-        vector.sort_by(|a, b| a.0.cmp(&b.0));
+    #[export]
+    fn can_redo(&self, _owner: &Reference) -> bool {
+        match self.root {
+            SystemTreeRoot::Grav(ref tree) => tree.can_redo(),
+            SystemTreeRoot::None => false,
+        }
+    }
 
+    /// Whether the tree was edited after it was solved, and needs to be solved again.
+    #[export]
+    fn is_stale(&self, _owner: &Reference) -> bool {
+        match self.root {
+            SystemTreeRoot::Grav(ref tree) => tree.is_stale(),
+            SystemTreeRoot::None => false,
+        }
+    }
+
+    /// Schedules a change in velocity of a body. Returns false if the tree can't be edited.
+    #[export]
+    fn schedule_impulse(&mut self, _owner: &Reference, time: f64, index: i64, dv: Vector3) -> bool {
+        let dv = DVec3::new(dv.x as f64, dv.y as f64, dv.z as f64);
+
+        match usize::try_from(index) {
+            Ok(index) => self.schedule(time, GravitationalEvent::Impulse { index, dv }),
+            Err(_) => false,
+        }
+    }
+
+    #[export]
+    fn schedule_spawn(
+        &mut self,
+        _owner: &Reference,
+        time: f64,
+        index: i64,
+        pos: Vector3,
+        vel: Vector3,
+        mass: f64,
+    ) -> bool {
+        let index = match usize::try_from(index) {
+            Ok(index) => index,
+            Err(_) => return false,
+        };
+
+        self.schedule(
+            time,
+            GravitationalEvent::Spawn(NBody {
+                index,
+                pos: DVec3::new(pos.x as f64, pos.y as f64, pos.z as f64),
+                vel: DVec3::new(vel.x as f64, vel.y as f64, vel.z as f64),
+                mass,
+            }),
+        )
+    }
+
+    #[export]
+    fn schedule_despawn(&mut self, _owner: &Reference, time: f64, index: i64) -> bool {
+        match usize::try_from(index) {
+            Ok(index) => self.schedule(time, GravitationalEvent::Despawn { index }),
+            Err(_) => false,
+        }
+    }
+
+    #[export]
+    fn clear_schedule(&mut self, _owner: &Reference) {
+        if let SystemTreeRoot::Grav(ref mut tree) = self.root {
+            tree.schedule_mut().clear();
+        }
+    }
+
+    /// Returns the state of every body at the given time as pool arrays ordered by body:
+    /// `subsystems`, `ids`, `positions`, `velocities`, `masses` and horizon `radii`. Body ids are
+    /// only unique within a subsystem, so a body is identified by its entry in `subsystems` and
+    /// `ids` together. Passing the dictionary returned by the previous call reuses its arrays
+    /// instead of allocating new ones.
+    #[export]
+    fn frame(
+        &mut self,
+        _owner: &Reference,
+        time: f64,
+        #[opt] frame: Dictionary<Shared>,
+    ) -> Dictionary<Shared> {
+        let states = self.view_states(time);
+
+        let mut subsystems = take_array::<i32>(&frame, "subsystems", states.len());
+        let mut ids = take_array::<i32>(&frame, "ids", states.len());
+        let mut positions = take_array::<Vector3>(&frame, "positions", states.len());
+        let mut velocities = take_array::<Vector3>(&frame, "velocities", states.len());
+        let mut masses = take_array::<f32>(&frame, "masses", states.len());
+        let mut radii = take_array::<f32>(&frame, "radii", states.len());
+
+        {
+            let mut subsystems = subsystems.write();
+            let mut ids = ids.write();
+            let mut positions = positions.write();
+            let mut velocities = velocities.write();
+            let mut masses = masses.write();
+            let mut radii = radii.write();
+
+            for (i, (id, view)) in states.iter().enumerate() {
+                subsystems[i] = id.subsystem.id() as i32;
+                ids[i] = id.index as i32;
+                positions[i] = to_vector3(view.pos);
+                velocities[i] = to_vector3(view.vel);
+                masses[i] = view.mass as f32;
+                radii[i] = view.horizon_radius as f32;
+            }
+        }
+
+        put_array(&frame, "subsystems", subsystems);
+        put_array(&frame, "ids", ids);
+        put_array(&frame, "positions", positions);
+        put_array(&frame, "velocities", velocities);
+        put_array(&frame, "masses", masses);
+        put_array(&frame, "radii", radii);
+
+        frame
+    }
+
+    /// Returns the path of the body identified by `subsystem` and `index`, as in `frame`, between
+    /// `start` and `end` as a dictionary with its `points`, sampled with at most `max_points`
+    /// points where it bends the most. With `with_times` it also holds the `times` the body passed
+    /// each point, for fading the trail. Windows with `end` before `start` give the same points,
+    /// still ordered by increasing time.
+    #[export]
+    fn trail(
+        &self,
+        _owner: &Reference,
+        subsystem: i64,
+        index: i64,
+        start: f64,
+        end: f64,
+        max_points: i64,
+        #[opt] with_times: bool,
+    ) -> Dictionary<Unique> {
+        let points = match (self.body_id(subsystem, index), usize::try_from(max_points)) {
+            (Some(id), Ok(max_points)) => self.trail_points(id, start, end, max_points),
+            _ => Vec::new(),
+        };
+
+        let dict = Dictionary::new();
+
+        dict.insert(
+            "points",
+            points
+                .iter()
+                .map(|point| to_vector3(point.pos))
+                .collect::<Vector3Array>(),
+        );
+
+        if with_times {
+            dict.insert(
+                "times",
+                points
+                    .iter()
+                    .map(|point| point.time as f32)
+                    .collect::<Float32Array>(),
+            );
+        }
+
+        dict
+    }
+
+    /// Returns the body picked by a ray at `time` as a dictionary with its `subsystem` and
+    /// `index`, as in `view`, or an empty dictionary if the ray misses. A body is hit within
+    /// `tolerance` world units of the ray, or with `angular` within `tolerance` radians as seen
+    /// from its origin, which is a fixed distance on screen. Of the bodies hit, the one closest to
+    /// the ray relative to its tolerance is picked.
+    #[export]
+    fn pick(
+        &mut self,
+        _owner: &Reference,
+        origin: Vector3,
+        direction: Vector3,
+        time: f64,
+        tolerance: f64,
+        #[opt] angular: bool,
+    ) -> Dictionary<Unique> {
+        let tolerance = if angular {
+            RayTolerance {
+                world: 0.0,
+                angular: tolerance,
+            }
+        } else {
+            RayTolerance {
+                world: tolerance,
+                angular: 0.0,
+            }
+        };
+
+        let dict = Dictionary::new();
+
+        if let Some(hit) =
+            self.spatial_index(time)
+                .ray(to_dvec3(origin), to_dvec3(direction), tolerance)
+        {
+            dict.insert("subsystem", hit.id.subsystem.id() as i64);
+            dict.insert("index", hit.id.index as i64);
+        }
+
+        dict
+    }
+
+    /// Returns the `k` bodies nearest to `point` at `time`, nearest first, as a dictionary of
+    /// `subsystems` and `ids` arrays which identify the bodies together, as in `frame`.
+    #[export]
+    fn nearest_bodies(
+        &mut self,
+        _owner: &Reference,
+        point: Vector3,
+        time: f64,
+        k: i64,
+    ) -> Dictionary<Unique> {
+        let k = usize::try_from(k).unwrap_or(0);
+
+        to_body_ids(self.spatial_index(time).nearest(to_dvec3(point), k))
+    }
+
+    /// Returns the bodies within `radius` of `point` at `time`, nearest first, in the same form
+    /// as `nearest_bodies`.
+    #[export]
+    fn bodies_within(
+        &mut self,
+        _owner: &Reference,
+        point: Vector3,
+        time: f64,
+        radius: f64,
+    ) -> Dictionary<Unique> {
+        to_body_ids(self.spatial_index(time).within(to_dvec3(point), radius))
+    }
+
+    /// Returns the state of every body at the given time, ordered by body. Each entry is a
+    /// dictionary with the subsystem and index which identify the body, and its position,
+    /// velocity, mass, horizon radius and visibility.
+    #[export]
+    fn view(&mut self, _owner: &Reference, time: f64) -> VariantArray<Unique> {
+        let array = VariantArray::new();
+
+        for (id, view) in self.view_states(time) {
+            let dict = Dictionary::new();
+            dict.insert("subsystem", id.subsystem.id() as i64);
+            dict.insert("index", id.index as i64);
+            dict.insert("position", to_vector3(view.pos));
+            dict.insert("velocity", to_vector3(view.vel));
+            dict.insert("mass", view.mass);
+            dict.insert("horizon_radius", view.horizon_radius);
+            dict.insert("visible", view.visible);
+            array.push(dict.into_shared());
+        }
+
+        array
+    }
+
+    /// Ends the view started by `view`, `positions` or `horizon_radii`.
+    #[export]
+    fn end_view(&mut self, _owner: &Reference) {
+        if let SystemTreeRoot::Grav(ref mut tree) = self.root {
+            tree.view_end();
+        }
+    }
+
+    #[export]
+    fn positions(&mut self, _owner: &Reference, time: f64) -> VariantArray<Unique> {
         let array = VariantArray::new();
 
-        for v in vector {
-            array.push(Vector3::new(v.1.x as f32, v.1.y as f32, v.1.z as f32));
+        for (_index, view) in self.view_states(time) {
+            array.push(to_vector3(view.pos));
         }
 
         array
     }
+
+    /// Returns the horizon radius of every body at the given time, ordered like `positions`.
+    /// Bodies which are not black holes have a radius of zero.
+    #[export]
+    fn horizon_radii(&mut self, _owner: &Reference, time: f64) -> VariantArray<Unique> {
+        let array = VariantArray::new();
+
+        for (_index, view) in self.view_states(time) {
+            array.push(view.horizon_radius);
+        }
+
+        array
+    }
+}
+
+fn to_vector3(v: DVec3) -> Vector3 {
+    Vector3::new(v.x as f32, v.y as f32, v.z as f32)
+}
+
+fn to_dvec3(v: Vector3) -> DVec3 {
+    DVec3::new(v.x as f64, v.y as f64, v.z as f64)
+}
+
+/// Splits the ids of bodies found by a query into parallel `subsystems` and `ids` arrays.
+fn to_body_ids(found: Vec<(BodyId, f64)>) -> Dictionary<Unique> {
+    let subsystems = found
+        .iter()
+        .map(|(id, _distance)| id.subsystem.id() as i32)
+        .collect::<Int32Array>();
+    let ids = found
+        .iter()
+        .map(|(id, _distance)| id.index as i32)
+        .collect::<Int32Array>();
+
+    let dict = Dictionary::new();
+    dict.insert("subsystems", subsystems);
+    dict.insert("ids", ids);
+    dict
+}
+
+fn to_outline(items: &[Inspected]) -> VariantArray<Unique> {
+    let array = VariantArray::new();
+
+    for item in items {
+        let components = Dictionary::new();
+        for component in &item.components {
+            let fields = Dictionary::new();
+            for (name, value) in &component.fields {
+                fields.insert(*name, to_variant(value));
+            }
+            components.insert(component.name, fields.into_shared());
+        }
+
+        let dict = Dictionary::new();
+        dict.insert("id", item.id as i64);
+        dict.insert("type", item.kind);
+        dict.insert("child_count", item.child_count as i64);
+        dict.insert("components", components.into_shared());
+        dict.insert("children", to_outline(&item.children).into_shared());
+        array.push(dict.into_shared());
+    }
+
+    array
+}
+
+fn to_variant(value: &Value) -> Variant {
+    match value {
+        Value::Bool(value) => value.to_variant(),
+        Value::Int(value) => value.to_variant(),
+        Value::Float(value) => value.to_variant(),
+        Value::Vector(value) => to_vector3(*value).to_variant(),
+        Value::Text(value) => value.to_variant(),
+    }
+}
+
+/// Takes the array stored under `key` out of a frame, resized to `len`. The entry is cleared so
+/// the array is uniquely owned and can be written without being copied.
+fn take_array<T: Element>(frame: &Dictionary<Shared>, key: &str, len: usize) -> TypedArray<T> {
+    let mut array = if frame.contains(key) {
+        let array = TypedArray::<T>::from_variant(&frame.get(key)).unwrap_or_default();
+        frame.update(key, Variant::new());
+        array
+    } else {
+        TypedArray::new()
+    };
+
+    array.resize(len as i32);
+    array
+}
+
+fn put_array<T: Element>(frame: &Dictionary<Shared>, key: &str, array: TypedArray<T>) {
+    if frame.contains(key) {
+        frame.update(key, array);
+    } else {
+        unsafe { frame.insert(key, array) };
+    }
+}
+
+impl SystemTreeGD {
+    /// Whether the tree has been moved to a worker thread by `solve`.
+    pub fn solve_running(&self) -> bool {
+        self.job.is_some()
+    }
+
+    fn edit<C: Command<GravitationalSystem> + 'static>(&mut self, command: C) -> bool {
+        self.with_tree(|tree| tree.edit(command))
+    }
+
+    /// Runs an edit on the tree, reporting failures to Godot. Fails while the tree is solving.
+    fn with_tree<F>(&mut self, f: F) -> bool
+    where
+        F: FnOnce(&mut SystemTree<GravitationalSystem>) -> Result<(), EditError>,
+    {
+        let result = match self.root {
+            SystemTreeRoot::Grav(ref mut tree) => f(tree),
+            SystemTreeRoot::None if self.job.is_some() => Err(EditError::Solving),
+            SystemTreeRoot::None => return false,
+        };
+
+        match result {
+            Ok(()) => true,
+            Err(error) => {
+                godot_error!("Failed to edit system with error {:?}", error);
+                false
+            }
+        }
+    }
+
+    fn schedule(&mut self, time: f64, event: GravitationalEvent) -> bool {
+        match self.root {
+            SystemTreeRoot::Grav(ref mut tree) if time.is_finite() => {
+                tree.schedule_mut().insert(time, Event::System(event));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The body identified by a subsystem and index as given to Godot, if the tree has that
+    /// subsystem.
+    fn body_id(&self, subsystem: i64, index: i64) -> Option<BodyId> {
+        let tree = match self.root {
+            SystemTreeRoot::Grav(ref tree) => tree,
+            SystemTreeRoot::None => return None,
+        };
+
+        let subsystem = gravity::find_subsystem(tree.root(), u32::try_from(subsystem).ok()?)?;
+        let index = usize::try_from(index).ok()?;
+
+        Some(BodyId { subsystem, index })
+    }
+
+    /// Views the tree at `time`, returning the view state of every body ordered by id.
+    fn view_states(&mut self, time: f64) -> Vec<(BodyId, ViewState)> {
+        match self.root {
+            SystemTreeRoot::Grav(ref mut tree) => {
+                tree.view(time);
+                gravity::view_states(tree.root())
+            }
+            SystemTreeRoot::None => Vec::new(),
+        }
+    }
+
+    /// Indexes the bodies visible at `time` by their position.
+    fn spatial_index(&mut self, time: f64) -> SpatialIndex<BodyId> {
+        SpatialIndex::new(
+            self.view_states(time)
+                .into_iter()
+                .filter(|(_id, view)| view.visible)
+                .map(|(id, view)| (id, view.pos))
+                .collect(),
+        )
+    }
+
+    fn trail_points(&self, id: BodyId, start: f64, end: f64, max_points: usize) -> Vec<TrailPoint> {
+        let tree = match self.root {
+            SystemTreeRoot::Grav(ref tree) => tree,
+            SystemTreeRoot::None => return Vec::new(),
+        };
+
+        let subsystem = match tree.root().children().get::<Subsystem>(id.subsystem) {
+            Ok(subsystem) => subsystem,
+            Err(_) => return Vec::new(),
+        };
+
+        let nbody = match subsystem.downcast_ref::<NBodySystem>() {
+            Some(nbody) => nbody,
+            None => return Vec::new(),
+        };
+
+        let mut bodies = nbody
+            .children()
+            .query::<(&NBody, &ContinuousRecord<Position>)>();
+
+        let points = bodies
+            .iter()
+            .find(|(_e, (body, _record))| body.index == id.index)
+            .map(|(_e, (_body, record))| trail::trail(record, start, end, max_points))
+            .unwrap_or_default();
+
+        points
+    }
+
+    fn fail_solve(&self, owner: &Reference, message: String) -> bool {
+        owner.emit_signal("solve_failed", &[message.to_variant()]);
+        false
+    }
+
+    fn solved_end(&self) -> Option<f64> {
+        match self.root {
+            SystemTreeRoot::Grav(ref tree) => tree.solved_to(),
+            SystemTreeRoot::None => None,
+        }
+    }
 }
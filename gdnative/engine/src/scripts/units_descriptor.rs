@@ -1,5 +1,19 @@
+use crate::global::{Length, Mass, Time, Units};
 use gdnative::prelude::*;
+use thiserror::Error;
 
+#[derive(Debug, Error)]
+pub enum UnitsError {
+    #[error("Unknown length unit {0}")]
+    UnknownLength(i32),
+    #[error("Unknown time unit {0}")]
+    UnknownTime(i32),
+    #[error("Unknown mass unit {0}")]
+    UnknownMass(i32),
+}
+
+/// Units as option ids of the interface. Each id is the position of the unit in the list of
+/// names returned for its quantity.
 #[derive(NativeClass, Clone)]
 #[inherit(Reference)]
 pub struct UnitsDescriptor {
@@ -16,6 +30,64 @@ impl UnitsDescriptor {
     fn new(_owner: &Reference) -> Self {
         Self::default()
     }
+
+    #[export]
+    fn length_names(&self, _owner: &Reference) -> StringArray {
+        Length::ALL
+            .iter()
+            .map(|unit| GodotString::from_str(unit.name()))
+            .collect()
+    }
+
+    #[export]
+    fn time_names(&self, _owner: &Reference) -> StringArray {
+        Time::ALL
+            .iter()
+            .map(|unit| GodotString::from_str(unit.name()))
+            .collect()
+    }
+
+    #[export]
+    fn mass_names(&self, _owner: &Reference) -> StringArray {
+        Mass::ALL
+            .iter()
+            .map(|unit| GodotString::from_str(unit.name()))
+            .collect()
+    }
+
+    /// Whether every id refers to a known unit.
+    #[export]
+    fn is_valid(&self, _owner: &Reference) -> bool {
+        self.to_units().is_ok()
+    }
+}
+
+impl UnitsDescriptor {
+    /// Converts the option ids used by the interface into units.
+    pub fn to_units(&self) -> Result<Units, UnitsError> {
+        let length =
+            lookup(&Length::ALL, self.length).ok_or(UnitsError::UnknownLength(self.length))?;
+        let time = lookup(&Time::ALL, self.time).ok_or(UnitsError::UnknownTime(self.time))?;
+        let mass = lookup(&Mass::ALL, self.mass).ok_or(UnitsError::UnknownMass(self.mass))?;
+
+        Ok(Units::new(length, time, mass))
+    }
+
+    pub fn from_units(units: &Units) -> Self {
+        Self {
+            mass: position(&Mass::ALL, units.mass()),
+            length: position(&Length::ALL, units.length()),
+            time: position(&Time::ALL, units.time()),
+        }
+    }
+}
+
+fn lookup<T: Copy>(all: &[T], id: i32) -> Option<T> {
+    usize::try_from(id).ok().and_then(|id| all.get(id)).copied()
+}
+
+fn position<T: PartialEq>(all: &[T], unit: T) -> i32 {
+    all.iter().position(|other| *other == unit).unwrap_or(0) as i32
 }
 
 impl Default for UnitsDescriptor {
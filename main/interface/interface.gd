@@ -9,7 +9,8 @@ onready var config_some = $Config/Some
 onready var system_none = $System/None
 onready var system_some = $System/Some
 
-onready var tree = $System/Some/Tree
+onready var outline = $System/Some/Tree
+onready var units = $Config/Some/Units
 
 func _ready():
 	config_none.visible = true
@@ -18,21 +19,6 @@ func _ready():
 	system_none.visible = true
 	system_some.visible = false
 	
-	var root = tree.create_item()
-	root.set_text(0, "Gravitational System")
-	
-	var nbodies = tree.create_item(root)
-	nbodies.set_text(0, "NBody System")
-	
-	var red_giant = tree.create_item(nbodies)
-	red_giant.set_text(0, "Red Giant")
-	
-	var blackhole = tree.create_item(nbodies)
-	blackhole.set_text(0, "Blackhole")
-	
-	var white_dwarf = tree.create_item(nbodies)
-	white_dwarf.set_text(0, "White Dwarf")
-	
 func on_system_changed(tree, path):
 	if tree:
 		config_none.visible = false
@@ -40,11 +26,42 @@ func on_system_changed(tree, path):
 	
 		system_none.visible = false
 		system_some.visible = true
+		
+		_build_outline(tree)
+		units.set_tree(tree)
 	else:
 		config_none.visible = true
 		config_some.visible = false
 	
 		system_none.visible = true
 		system_some.visible = false
+		
+		units.set_tree(null)
+
+func _build_outline(tree):
+	outline.clear()
+	
+	var root = outline.create_item()
+	root.set_text(0, tree.name() + " (" + tree.root_type() + ")")
+	
+	for item in tree.outline():
+		_add_item(root, item)
+
+func _add_item(parent, item):
+	var node = outline.create_item(parent)
+	if item["type"] == "body":
+		node.set_text(0, "Body " + str(item["id"]))
+	else:
+		node.set_text(0, item["type"] + " (" + str(item["child_count"]) + ")")
 	
+	for component in item["components"]:
+		var fields = item["components"][component]
+		var component_node = outline.create_item(node)
+		component_node.set_text(0, component)
+		component_node.collapsed = true
+		for field in fields:
+			var field_node = outline.create_item(component_node)
+			field_node.set_text(0, field + ": " + str(fields[field]))
 	
+	for child in item["children"]:
+		_add_item(node, child)
@@ -1,17 +1,43 @@
 extends Node
 
+var tree
+
 onready var length = $VBox/Grid/LengthOptions
 onready var mass = $VBox/Grid/MassOptions
 onready var time = $VBox/Grid/TimeOptions
 
 func _ready():
-	length.add_item("Meters (m)", 0)
-	length.add_item("Kilometers (km)", 1)
-	length.add_item("Light Years (ly)", 2)
-	length.selected = 0
+	var desc = UnitsDescriptor.new()
+	_add_items(length, desc.length_names())
+	_add_items(mass, desc.mass_names())
+	_add_items(time, desc.time_names())
+	
+	length.connect("item_selected", self, "_on_units_selected")
+	mass.connect("item_selected", self, "_on_units_selected")
+	time.connect("item_selected", self, "_on_units_selected")
+
+func set_tree(new_tree):
+	tree = new_tree
+	if tree:
+		var desc = tree.units()
+		length.selected = desc.length
+		mass.selected = desc.mass
+		time.selected = desc.time
+
+func _add_items(options, names):
+	options.clear()
+	for i in range(names.size()):
+		options.add_item(names[i], i)
+	options.selected = 0
+
+func _on_units_selected(_index):
+	if !tree:
+		return
 	
-	mass.add_item("Kilograms (kg)", 0)
-	mass.selected = 0
+	var desc = UnitsDescriptor.new()
+	desc.length = length.get_selected_id()
+	desc.mass = mass.get_selected_id()
+	desc.time = time.get_selected_id()
 	
-	time.add_item("Seconds (s)", 0)
-	time.selected = 0
+	if !tree.set_units(desc):
+		set_tree(tree)
@@ -13,6 +13,7 @@ func _ready():
 	menu_bar.connect("system_opened", self, "_on_system_opened")
 	menu_bar.connect("system_closed", self, "_on_system_closed")
 	menu_bar.connect("system_saved", self, "_on_system_saved")
+	menu_bar.connect("system_solved", self, "_on_system_solved")
 	
 	views.connect("system_selected", self, "_on_system_selected")
 	
@@ -21,15 +22,23 @@ func on_system_changed(tree, path):
 	menu_bar.on_system_changed(tree, path)
 	interface.on_system_changed(tree, path)
 	
-func _on_system_created_grav(_desc):
+func _on_system_created_grav(desc):
 	print("Creating Gravitational System")
 	
+	var status = system_manager.create_grav(desc, UnitsDescriptor.new(), [])
+	if status["ok"]:
+		views.add_system(status["tree"], "")
+	else:
+		_show_error("Failed to Create System", status["message"])
+	
 func _on_system_opened(path):
 	print("Loading System from path: ", path)
 	
-	var tree = system_manager.load(path)
-	if !tree.is_none():
-		views.add_system(tree, path)
+	var status = system_manager.load(path)
+	if status["ok"]:
+		views.add_system(status["tree"], path)
+	else:
+		_show_error("Failed to Open System", status["message"])
 			
 func _on_system_closed():
 	views.close_current()
@@ -40,8 +49,28 @@ func _on_system_saved(path):
 		if path != null:
 			current[1] = path
 		
-		if current[1]:
-			system_manager.save(current[1], current[0])
+		if current[0].is_solving():
+			print("Cannot save while the system is solving")
+		elif current[1]:
+			var status = system_manager.save(current[1], current[0], true)
+			if status["ok"]:
+				print(status["message"])
+			else:
+				_show_error("Failed to Save System", status["message"])
+
+func _on_system_solved(desc):
+	var current = views.get_current()
+	if current != null:
+		print("Solving from ", desc.start_time, " to ", desc.end_time)
+		current[0].solve(desc)
 			
 func _on_system_selected(tree, path):
 	on_system_changed(tree, path)
+
+func _show_error(title, message):
+	var dialog = AcceptDialog.new()
+	dialog.window_title = title
+	dialog.dialog_text = message
+	dialog.connect("popup_hide", dialog, "queue_free")
+	add_child(dialog)
+	dialog.popup_centered()
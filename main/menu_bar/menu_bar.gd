@@ -4,6 +4,7 @@ signal system_created_grav(desc)
 signal system_opened(path)
 signal system_closed()
 signal system_saved(path)
+signal system_solved(desc)
 
 onready var system_menu = $Padding/HBox/System
 
@@ -12,6 +13,7 @@ func _ready():
 	system_menu.connect("system_opened", self, "_on_system_opened")
 	system_menu.connect("system_closed", self, "_on_system_closed")
 	system_menu.connect("system_saved", self, "_on_system_saved")
+	system_menu.connect("system_solved", self, "_on_system_solved")
 
 func on_system_changed(tree, path):
 	system_menu.on_system_changed(tree, path)
@@ -28,3 +30,5 @@ func _on_system_closed():
 func _on_system_saved(path):
 	emit_signal("system_saved", path)
 
+func _on_system_solved(desc):
+	emit_signal("system_solved", desc)
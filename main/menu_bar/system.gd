@@ -5,11 +5,14 @@ signal system_created_grav(desc)
 signal system_opened(path)
 signal system_closed()
 signal system_saved(path)
+signal system_solved(desc)
 
 # Variables
+var watched = null
 onready var popup = get_popup()
 onready var new = PopupMenu.new()
 onready var grav = $Gravitational
+onready var solve = $Solve
 onready var open = FileDialog.new()
 onready var save_as = FileDialog.new()
 
@@ -47,6 +50,11 @@ func _ready():
 	
 	popup.add_separator()
 	
+	# Solve
+	popup.add_item("Solve")
+	popup.set_item_disabled(7, true)
+	solve.connect("solved", self, "_on_solved")
+	
 	save_as.access = FileDialog.ACCESS_FILESYSTEM
 	save_as.mode = FileDialog.MODE_SAVE_FILE
 	save_as.resizable = true
@@ -58,14 +66,18 @@ func _ready():
 	popup.connect("id_pressed", self, "_on_item_pressed")
 	
 func on_system_changed(tree, path):
+	_watch(tree)
+	
 	if tree != null:
 		popup.set_item_disabled(2, false)
 		popup.set_item_disabled(4, path == null)
 		popup.set_item_disabled(5, false)
+		popup.set_item_disabled(7, tree.is_solving())
 	else:
 		popup.set_item_disabled(2, true)
 		popup.set_item_disabled(4, true)
 		popup.set_item_disabled(5, true)
+		popup.set_item_disabled(7, true)
 	
 func _on_item_pressed(ID):
 	match ID:
@@ -77,6 +89,8 @@ func _on_item_pressed(ID):
 			emit_signal("system_saved", null)
 		5:
 			save_as.popup_centered_minsize(Vector2(300.0, 200.0))
+		7:
+			solve.popup_centered()
 		_:
 			pass
 		
@@ -90,6 +104,32 @@ func _on_new_item_pressed(ID):
 func _on_grav_created(desc):
 	emit_signal("system_created_grav", desc)
 
+func _on_solved(desc):
+	# Re-enabled once the tree reports the solve has ended
+	popup.set_item_disabled(7, true)
+	emit_signal("system_solved", desc)
+
+# Follows the solves of the current tree, so Solve is only enabled while none is running
+func _watch(tree):
+	if tree == watched:
+		return
+	
+	if watched != null and is_instance_valid(watched):
+		watched.disconnect("solve_finished", self, "_on_solve_finished")
+		watched.disconnect("solve_failed", self, "_on_solve_failed")
+	
+	watched = tree
+	
+	if watched != null:
+		watched.connect("solve_finished", self, "_on_solve_finished")
+		watched.connect("solve_failed", self, "_on_solve_failed")
+
+func _on_solve_finished(_time, _cancelled):
+	popup.set_item_disabled(7, false)
+
+func _on_solve_failed(_message):
+	popup.set_item_disabled(7, false)
+
 func _on_file_opened(path):
 	emit_signal("system_opened", path)
 	
@@ -2,8 +2,15 @@ extends Node
 
 var tree
 var path
+var frame = {}
+var selected = {}
+
+# How far from a body a click may land and still select it, in pixels
+const PICK_PIXELS = 8.0
 
 onready var slider = $Time/HBox/HSlider
+onready var container = $ViewportContainer
+onready var camera = $ViewportContainer/Viewport/Camera
 
 onready var red_giant = $ViewportContainer/Viewport/RedGiant
 onready var blackhole = $ViewportContainer/Viewport/Blackhole
@@ -11,14 +18,54 @@ onready var white_dwarf = $ViewportContainer/Viewport/WhiteDwarf
 
 func _ready():
 	slider.connect("value_changed", self, "_on_slider_changed")
+	container.connect("gui_input", self, "_on_viewport_input")
+	tree.connect("solve_progressed", self, "_on_solve_progressed")
+	tree.connect("solve_finished", self, "_on_solve_finished")
+	tree.connect("solve_failed", self, "_on_solve_failed")
 	
 	_on_slider_changed(0.0)
 	
+func _process(_delta):
+	tree.poll()
+	
 
 func _on_slider_changed(time):
-	var positions = tree.positions(time)
+	if tree.is_solving():
+		return
+	
+	frame = tree.frame(time, frame)
+	var positions = frame["positions"]
+	if positions.size() < 3:
+		return
+	
 	red_giant.translation = positions[0]
 	blackhole.translation = positions[1]
 	white_dwarf.translation = positions[2]
 	
 
+func _on_viewport_input(event):
+	if not (event is InputEventMouseButton and event.pressed and event.button_index == BUTTON_LEFT):
+		return
+	if tree.is_solving():
+		return
+	
+	var origin = camera.project_ray_origin(event.position)
+	var direction = camera.project_ray_normal(event.position)
+	var angle = deg2rad(camera.fov) * PICK_PIXELS / container.rect_size.y
+	
+	selected = tree.pick(origin, direction, slider.value, angle, true)
+	if not selected.empty():
+		print("Selected body ", selected["index"], " of subsystem ", selected["subsystem"])
+
+func _on_solve_progressed(time, fraction, remaining_seconds):
+	print("Solved to ", time, " (", int(fraction * 100.0), "%, ", int(max(remaining_seconds, 0.0)), "s remaining)")
+	
+func _on_solve_finished(time, cancelled):
+	if cancelled:
+		print("Solve cancelled at ", time)
+	else:
+		print("Solve finished at ", time)
+	_on_slider_changed(slider.value)
+	
+func _on_solve_failed(message):
+	print("Solve failed: ", message)